@@ -0,0 +1,191 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+// Compares the substring search with a naive search on a synthetic file.
+//
+//     cargo bench --bench substring -- [SIZE_IN_MiB]
+//
+// The default size is 2048 MiB. The file is written to the temporary
+// directory and deleted afterwards. Only the time spent searching is counted,
+// not the time spent reading the file.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src/substring.rs"]
+mod substring;
+
+use substring::Finder;
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+const DEFAULT_SIZE_MIB: u64 = 2048;
+
+// None of the needles occurs in the data (it never contains 0xAB), so they
+// are all searched through the whole file.
+const NEEDLES: &[&[u8]] = &[
+    b"\xAB",
+    b"\x00\xAB",
+    b"fo\xAB",
+    b"\x00\x00\x00\x01\x00\x00\x00\xAB",
+    b"The quick brown fox jumps\xAB",
+    b"the quick brown fax",
+    b"\x7FELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x00\x3E\x00\x01\x00\x00\xAB",
+];
+
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// Mix of random bytes, zeros and English-ish text, so that both rare and
+// very frequent first bytes are covered.
+fn fill_block(block: &mut [u8], index: u64, state: &mut u64) {
+    const TEXT: &[u8] = b"Lorem ipsum dolor sit amet, the quick brown fox jumped over a lazy dog. ";
+    match index % 4 {
+        0 | 1 => {
+            for chunk in block.chunks_mut(8) {
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                let bytes = state.to_le_bytes();
+                for (byte, value) in chunk.iter_mut().zip(bytes) {
+                    // keep the single byte needle out of the data
+                    *byte = if value == 0xAB { 0xAC } else { value };
+                }
+            }
+        }
+        2 => block.fill(0),
+        _ => {
+            for (byte, value) in block.iter_mut().zip(TEXT.iter().cycle()) {
+                *byte = *value;
+            }
+        }
+    }
+}
+
+fn create_file(size: u64) -> std::io::Result<TempFile> {
+    let path = std::env::temp_dir().join(format!("hox-bench-{}.bin", std::process::id()));
+    let temp = TempFile(path);
+    let mut writer = BufWriter::new(File::create(&temp.0)?);
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut state = 0x2545F4914F6CDD1D;
+    let mut offset = 0;
+    let mut index = 0;
+    while offset < size {
+        let len = (size - offset).min(BLOCK_SIZE as u64) as usize;
+        fill_block(&mut block[..len], index, &mut state);
+        writer.write_all(&block[..len])?;
+        offset += len as u64;
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(temp)
+}
+
+// Reads the file in overlapping blocks from the front like hox does and
+// returns the match offset and the time spent in search.
+fn scan_forward(file: &mut File, len: usize, mut search: impl FnMut(&[u8]) -> Option<usize>) -> std::io::Result<(Option<u64>, Duration)> {
+    let mut buf = vec![0; BLOCK_SIZE + len - 1];
+    let mut elapsed = Duration::ZERO;
+    let mut offset = 0;
+    file.seek(SeekFrom::Start(0))?;
+    loop {
+        let count = read_full(file, &mut buf)?;
+        if count < len {
+            return Ok((None, elapsed));
+        }
+        let start = Instant::now();
+        let found = search(&buf[..count]);
+        elapsed += start.elapsed();
+        if let Some(index) = found {
+            return Ok((Some(offset + index as u64), elapsed));
+        }
+        offset += (count + 1 - len) as u64;
+        file.seek(SeekFrom::Start(offset))?;
+    }
+}
+
+fn scan_backward(file: &mut File, len: usize, mut search: impl FnMut(&[u8]) -> Option<usize>) -> std::io::Result<(Option<u64>, Duration)> {
+    let mut buf = vec![0; BLOCK_SIZE + len - 1];
+    let mut elapsed = Duration::ZERO;
+    let mut end_offset = file.metadata()?.len();
+    while end_offset >= len as u64 {
+        let offset = end_offset.saturating_sub(buf.len() as u64);
+        file.seek(SeekFrom::Start(offset))?;
+        let count = read_full(file, &mut buf[..(end_offset - offset) as usize])?;
+        let start = Instant::now();
+        let found = search(&buf[..count]);
+        elapsed += start.elapsed();
+        if let Some(index) = found {
+            return Ok((Some(offset + index as u64), elapsed));
+        }
+        if offset == 0 {
+            break;
+        }
+        end_offset = offset + len as u64 - 1;
+    }
+    Ok((None, elapsed))
+}
+
+fn read_full(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut count = 0;
+    while count < buf.len() {
+        let n = file.read(&mut buf[count..])?;
+        if n == 0 {
+            break;
+        }
+        count += n;
+    }
+    Ok(count)
+}
+
+fn report(name: &str, size: u64, result: (Option<u64>, Duration)) {
+    let (found, elapsed) = result;
+    let speed = size as f64 / (1024.0 * 1024.0 * 1024.0) / elapsed.as_secs_f64();
+    println!("    {:<10} {:>9.3} s {:>8.2} GiB/s  {:?}", name, elapsed.as_secs_f64(), speed, found);
+}
+
+fn main() -> std::io::Result<()> {
+    // cargo bench passes --bench
+    let size_mib = std::env::args().skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map(|arg| arg.parse().expect("size has to be a number of MiB"))
+        .unwrap_or(DEFAULT_SIZE_MIB);
+    let size = size_mib * 1024 * 1024;
+
+    println!("writing {} MiB synthetic file...", size_mib);
+    let temp = create_file(size)?;
+    let mut file = File::open(&temp.0)?;
+
+    for needle in NEEDLES {
+        let len = needle.len();
+        let finder = Finder::new(needle.to_vec());
+        println!("needle of {} bytes: {:02X?}", len, needle);
+
+        report("naive", size, scan_forward(&mut file, len, |data| data.windows(len).position(|window| window == *needle))?);
+        report("find", size, scan_forward(&mut file, len, |data| finder.find(data))?);
+        report("naive rev", size, scan_backward(&mut file, len, |data| data.windows(len).rposition(|window| window == *needle))?);
+        report("rfind", size, scan_backward(&mut file, len, |data| finder.rfind(data))?);
+        report("find_iter", size, scan_forward(&mut file, len, |data| finder.find_iter(data).last())?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,351 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::path::Path;
+use std::io::{Seek, SeekFrom};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::io::AsRawFd;
+
+use crate::mapped::Mapped;
+use crate::paged::Paged;
+use crate::spool::Spool;
+use crate::process::ProcessMemory;
+use crate::result::{Result, Error};
+
+// _IOR(0x12, 114, size_t)
+#[cfg(all(target_os = "linux", not(any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64"))))]
+const BLKGETSIZE64: u32 = 0x80081272;
+
+#[cfg(all(target_os = "linux", any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64")))]
+const BLKGETSIZE64: u32 = 0x40081272;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileChange {
+    Unchanged,
+    Grew,
+    Shrank,
+    Replaced,
+}
+
+// Where the original (unmodified) data comes from.
+pub enum Backing {
+    Empty(File),
+    Mapped(Mapped),
+    Paged(Paged),
+    Spool(Spool),
+    Process(ProcessMemory),
+}
+
+impl Backing {
+    pub fn open(file: File) -> Result<Self> {
+        let meta = file.metadata()?;
+        let file_type = meta.file_type();
+        if file_type.is_fifo() {
+            return Ok(Backing::Spool(Spool::new(file)?));
+        }
+
+        let size = if file_type.is_block_device() || file_type.is_char_device() {
+            device_size(&file)?
+        } else {
+            meta.len()
+        };
+
+        if size == 0 {
+            return Ok(Backing::Empty(file));
+        }
+
+        match Mapped::new(file.try_clone()?, size) {
+            Ok(mapped) => Ok(Backing::Mapped(mapped)),
+            // not every device supports mmap()
+            Err(_) => Ok(Backing::Paged(Paged::new(file, size))),
+        }
+    }
+
+    // A second handle to the same data, e.g. for searching in another thread.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(match self {
+            Backing::Empty(file)     => Backing::Empty(file.try_clone()?),
+            Backing::Mapped(mapped)  => Backing::Mapped(mapped.try_clone()?),
+            Backing::Paged(paged)    => Backing::Paged(paged.try_clone()?),
+            Backing::Spool(spool)    => Backing::Spool(spool.try_clone()?),
+            Backing::Process(memory) => Backing::Process(memory.try_clone()?),
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> u64 {
+        match self {
+            Backing::Empty(_)        => 0,
+            Backing::Mapped(mapped)  => mapped.size(),
+            Backing::Paged(paged)    => paged.size(),
+            Backing::Spool(spool)    => spool.len(),
+            Backing::Process(memory) => memory.size(),
+        }
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            Backing::Empty(_) => {
+                if buf.is_empty() {
+                    Ok(())
+                } else {
+                    Err(std::io::ErrorKind::UnexpectedEof.into())
+                }
+            }
+            Backing::Mapped(mapped) => mapped.read(offset, buf),
+            Backing::Paged(paged) => paged.read(offset, buf),
+            Backing::Spool(spool) => spool.read(offset, buf),
+            Backing::Process(memory) => memory.read(offset, buf),
+        }
+    }
+
+    // Checks whether the file at path changed and remaps it if its size did or
+    // if it was truncated under the mapping.
+    pub fn check(&mut self, path: &Path) -> Result<FileChange> {
+        let file = match self {
+            Backing::Empty(file) => file,
+            Backing::Mapped(mapped) => mapped.file(),
+            Backing::Paged(paged) => paged.file(),
+            Backing::Spool(_) | Backing::Process(_) => return Ok(FileChange::Unchanged),
+        };
+
+        let meta = file.metadata()?;
+        let path_meta = std::fs::metadata(path)?;
+        if meta.dev() != path_meta.dev() || meta.ino() != path_meta.ino() {
+            return Ok(FileChange::Replaced);
+        }
+
+        self.remap()
+    }
+
+    // Remaps the open file if its size changed or if it was truncated under
+    // the mapping, no matter if it is still the one at the path.
+    pub fn remap(&mut self) -> Result<FileChange> {
+        let old_size = self.len();
+        let faulted  = self.is_faulted();
+        let file = match self {
+            Backing::Empty(file) => file,
+            Backing::Mapped(mapped) => mapped.file(),
+            Backing::Paged(paged) => paged.file(),
+            Backing::Spool(_) | Backing::Process(_) => return Ok(FileChange::Unchanged),
+        };
+
+        let meta = file.metadata()?;
+        if !meta.is_file() {
+            return Ok(FileChange::Unchanged);
+        }
+
+        let size = meta.len();
+        if size == old_size && !faulted {
+            return Ok(FileChange::Unchanged);
+        }
+
+        // Can't fail for mappings, so a fault is never left behind.
+        match self {
+            Backing::Empty(file) => {
+                let file = file.try_clone()?;
+                *self = Backing::open(file)?;
+            }
+            Backing::Mapped(mapped) => mapped.remap(size),
+            Backing::Paged(paged) => paged.remap(size),
+            Backing::Spool(_) | Backing::Process(_) => {}
+        }
+
+        Ok(if size > old_size {
+            FileChange::Grew
+        } else if size < old_size {
+            FileChange::Shrank
+        } else {
+            FileChange::Unchanged
+        })
+    }
+
+    // The file got truncated under our mapping and needs to be remapped.
+    #[inline]
+    pub fn is_faulted(&self) -> bool {
+        match self {
+            Backing::Mapped(mapped) => mapped.is_faulted(),
+            _ => false,
+        }
+    }
+
+    // Called after the file was written to.
+    pub fn invalidate(&self) {
+        if let Backing::Paged(paged) = self {
+            paged.invalidate();
+        }
+    }
+
+    // The file that is shown, if changes can be written back to it.
+    pub fn file(&self) -> Result<&File> {
+        match self {
+            Backing::Empty(file) => Ok(file),
+            Backing::Mapped(mapped) => Ok(mapped.file()),
+            Backing::Paged(paged) => Ok(paged.file()),
+            Backing::Spool(_) => Err(Error::message("cannot save a stream, write the selection to a file instead")),
+            Backing::Process(_) => Err(Error::message("cannot save process memory")),
+        }
+    }
+
+    // Whether the size of the data may change when saving.
+    pub fn can_resize(&self) -> bool {
+        match self {
+            Backing::Empty(file) => file.metadata().map(|meta| meta.is_file()).unwrap_or(false),
+            Backing::Mapped(mapped) => mapped.file().metadata().map(|meta| meta.is_file()).unwrap_or(false),
+            Backing::Paged(paged) => paged.file().metadata().map(|meta| meta.is_file()).unwrap_or(false),
+            Backing::Spool(_) => true,
+            Backing::Process(_) => false,
+        }
+    }
+
+    #[inline]
+    pub fn process(&self) -> Option<&ProcessMemory> {
+        match self {
+            Backing::Process(memory) => Some(memory),
+            _ => None,
+        }
+    }
+
+    // The address shown for an offset.
+    #[inline]
+    pub fn address(&self, offset: u64) -> u64 {
+        match self {
+            Backing::Process(memory) => memory.address(offset),
+            _ => offset,
+        }
+    }
+
+    #[inline]
+    pub fn is_growing(&self) -> bool {
+        match self {
+            Backing::Spool(spool) => !spool.is_done(),
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        match self {
+            Backing::Spool(spool) => spool.take_error(),
+            _ => None,
+        }
+    }
+}
+
+// Block devices report a size of 0 in their metadata.
+fn device_size(file: &File) -> std::io::Result<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut size: u64 = 0;
+        if unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64 as _, &mut size) } == 0 {
+            return Ok(size);
+        }
+    }
+
+    let mut file = file;
+    let size = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::FromRawFd;
+    use std::path::PathBuf;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("hox-test-{}-{}", std::process::id(), name));
+            std::fs::write(&path, data).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn open(path: &Path) -> Backing {
+        Backing::open(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn device_size_falls_back_to_seek() {
+        // BLKGETSIZE64 fails for anything but block devices
+        let file = TempFile::new("device-size", &[0; 1234]);
+        let mut handle = File::open(&file.0).unwrap();
+        assert_eq!(device_size(&handle).unwrap(), 1234);
+        assert_eq!(handle.stream_position().unwrap(), 0);
+
+        assert_eq!(device_size(&File::open("/dev/null").unwrap()).unwrap(), 0);
+    }
+
+    #[test]
+    fn open_kinds() {
+        let empty = TempFile::new("backing-empty", b"");
+        assert!(matches!(open(&empty.0), Backing::Empty(_)));
+
+        let file = TempFile::new("backing-mapped", b"data");
+        let backing = open(&file.0);
+        assert!(matches!(backing, Backing::Mapped(_)));
+        assert!(backing.can_resize());
+        let mut buf = [0; 4];
+        backing.read(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"data");
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (input, output) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        drop(output);
+        let backing = Backing::open(input).unwrap();
+        assert!(matches!(backing, Backing::Spool(_)));
+        assert!(backing.file().is_err());
+    }
+
+    #[test]
+    fn check_grow_shrink_replace() {
+        let file = TempFile::new("backing-check", b"0123456789");
+        let mut backing = open(&file.0);
+        assert_eq!(backing.check(&file.0).unwrap(), FileChange::Unchanged);
+
+        let mut handle = std::fs::OpenOptions::new().append(true).open(&file.0).unwrap();
+        std::io::Write::write_all(&mut handle, b"abc").unwrap();
+        assert_eq!(backing.check(&file.0).unwrap(), FileChange::Grew);
+        assert_eq!(backing.len(), 13);
+        let mut buf = [0; 3];
+        backing.read(10, &mut buf).unwrap();
+        assert_eq!(&buf, b"abc");
+
+        handle.set_len(5).unwrap();
+        assert_eq!(backing.check(&file.0).unwrap(), FileChange::Shrank);
+        assert_eq!(backing.len(), 5);
+        assert!(backing.read(4, &mut buf).is_err());
+
+        handle.set_len(0).unwrap();
+        assert_eq!(backing.check(&file.0).unwrap(), FileChange::Shrank);
+        assert_eq!(backing.len(), 0);
+
+        let other = TempFile::new("backing-check-new", b"new");
+        std::fs::rename(&other.0, &file.0).unwrap();
+        assert_eq!(backing.check(&file.0).unwrap(), FileChange::Replaced);
+    }
+}
@@ -0,0 +1,138 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cell::RefCell;
+use std::cmp::min;
+
+pub trait Block {
+    fn size(&self) -> usize;
+    fn read(&self, offset: usize, buf: &mut [u8]) -> std::io::Result<()>;
+}
+
+impl Block for Vec<u8> {
+    #[inline]
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn read(&self, offset: usize, buf: &mut [u8]) -> std::io::Result<()> {
+        buf.copy_from_slice(&self[offset..offset + buf.len()]);
+        Ok(())
+    }
+}
+
+struct Entry<B> {
+    index: u64,
+    block: B,
+}
+
+// Keeps the most recently used aligned blocks of some data of the given size.
+// Missing blocks are loaded by the closure passed to read(), which gets the
+// offset and size of the block.
+pub struct BlockCache<B: Block> {
+    block_size: u64,
+    capacity:   usize,
+    entries:    RefCell<Vec<Entry<B>>>,
+}
+
+impl<B: Block> BlockCache<B> {
+    pub fn new(block_size: u64, capacity: usize) -> Self {
+        Self {
+            block_size,
+            capacity,
+            entries: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn read<F>(&self, size: u64, offset: u64, buf: &mut [u8], load: F) -> std::io::Result<()>
+    where F: Fn(u64, usize) -> std::io::Result<B> {
+        if offset > size || buf.len() as u64 > size - offset {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut entries = self.entries.borrow_mut();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let data_offset = offset + pos as u64;
+            let index = data_offset / self.block_size;
+            let skip  = (data_offset % self.block_size) as usize;
+
+            // most recently used blocks are at the end
+            if let Some(entry_pos) = entries.iter().position(|entry| entry.index == index) {
+                let entry = entries.remove(entry_pos);
+                entries.push(entry);
+            } else {
+                let block_offset = index * self.block_size;
+                let block = load(block_offset, min(self.block_size, size - block_offset) as usize)?;
+                if entries.len() >= self.capacity {
+                    entries.remove(0);
+                }
+                entries.push(Entry { index, block });
+            }
+            let block = &entries.last().unwrap().block;
+
+            let count = min(block.size() - skip, buf.len() - pos);
+            block.read(skip, &mut buf[pos..pos + count])?;
+            pos += count;
+        }
+
+        Ok(())
+    }
+
+    pub fn any(&self, pred: impl Fn(&B) -> bool) -> bool {
+        self.entries.borrow().iter().any(|entry| pred(&entry.block))
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn reads_across_blocks_and_evicts_oldest() {
+        let data: Vec<u8> = (0..100).collect();
+        let loads = Cell::new(0);
+        let load = |offset: u64, size: usize| {
+            loads.set(loads.get() + 1);
+            Ok(data[offset as usize..offset as usize + size].to_vec())
+        };
+        let cache = BlockCache::new(10, 2);
+
+        let mut buf = [0; 15];
+        cache.read(100, 5, &mut buf, load).unwrap();
+        assert_eq!(buf[..], data[5..20]);
+        assert_eq!(loads.get(), 2);
+
+        // last block is shorter
+        let mut buf = [0; 5];
+        cache.read(95, 90, &mut buf, load).unwrap();
+        assert_eq!(buf[..], data[90..95]);
+        assert_eq!(loads.get(), 3);
+
+        // block 1 is still cached, block 0 got evicted
+        cache.read(100, 10, &mut buf, load).unwrap();
+        assert_eq!(loads.get(), 3);
+        cache.read(100, 0, &mut buf, load).unwrap();
+        assert_eq!(loads.get(), 4);
+
+        assert!(cache.read(100, 98, &mut buf, load).is_err());
+    }
+}
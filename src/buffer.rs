@@ -0,0 +1,1063 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::Write;
+use std::path::Path;
+use std::cmp::min;
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::ffi::OsString;
+use std::os::unix::fs::{FileExt, MetadataExt};
+
+use crate::backing::{Backing, FileChange};
+use crate::journal::{Journal, Change};
+use crate::result::{Result, Error};
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Source {
+    Original,
+    Added,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Piece {
+    pub source: Source,
+    pub offset: u64,
+    pub len:    u64,
+}
+
+impl Piece {
+    #[inline]
+    fn end_offset(&self) -> u64 {
+        self.offset + self.len
+    }
+
+    #[inline]
+    fn skip(&self, count: u64) -> Piece {
+        Piece {
+            source: self.source,
+            offset: self.offset + count,
+            len:    self.len - count,
+        }
+    }
+}
+
+pub fn pieces_len(pieces: &[Piece]) -> u64 {
+    pieces.iter().map(|piece| piece.len).sum()
+}
+
+// The append-only store of added bytes. It is kept in chunks that are shared
+// with snapshots, so taking one copies no data and appending afterwards copies
+// at most the last chunk.
+#[derive(Clone, Default)]
+struct Added {
+    chunks: Vec<Arc<Vec<u8>>>,
+    len:    u64,
+}
+
+impl Added {
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    // Appends len bytes that are filled in by fill, in one or more parts.
+    fn extend_with(&mut self, len: u64, mut fill: impl FnMut(&mut [u8]) -> std::io::Result<()>) -> std::io::Result<()> {
+        let mut remaining = len;
+        while remaining > 0 {
+            if self.chunks.last().map(|chunk| chunk.len() == BLOCK_SIZE).unwrap_or(true) {
+                self.chunks.push(Arc::new(Vec::with_capacity(BLOCK_SIZE)));
+            }
+            let chunk = Arc::make_mut(self.chunks.last_mut().unwrap());
+            let start = chunk.len();
+            let count = min((BLOCK_SIZE - start) as u64, remaining) as usize;
+            chunk.resize(start + count, 0);
+            self.len  += count as u64;
+            remaining -= count as u64;
+            fill(&mut chunk[start..])?;
+        }
+        Ok(())
+    }
+
+    fn extend_from_slice(&mut self, mut data: &[u8]) {
+        let _ = self.extend_with(data.len() as u64, |buf| {
+            buf.copy_from_slice(&data[..buf.len()]);
+            data = &data[buf.len()..];
+            Ok(())
+        });
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) {
+        let mut pos = 0;
+        while pos < buf.len() {
+            let added_offset = offset + pos as u64;
+            let chunk = &self.chunks[(added_offset / BLOCK_SIZE as u64) as usize];
+            let skip  = (added_offset % BLOCK_SIZE as u64) as usize;
+            let count = min(chunk.len() - skip, buf.len() - pos);
+            buf[pos..pos + count].copy_from_slice(&chunk[skip..skip + count]);
+            pos += count;
+        }
+    }
+}
+
+// A piece table over the read-only original data. All edits go into an
+// append-only buffer, so pieces (e.g. in the undo journal) never become
+// invalid.
+pub struct Buffer {
+    backing: Backing,
+    // how much of the backing is known to the pieces
+    original_len: u64,
+    added: Added,
+    pieces: Vec<Piece>,
+    starts: Vec<u64>,
+    len: u64,
+    // pieces as they are on disk
+    saved: Vec<Piece>,
+    // all source ranges referenced by saved, sorted and merged
+    saved_ranges: Vec<Piece>,
+    // whether the file on disk still is the mapped file
+    original_on_disk: bool,
+    // reads don't fail, missing data reads as zeros and the error is kept here
+    error: RefCell<Option<std::io::Error>>,
+}
+
+impl Buffer {
+    pub fn new(backing: Backing) -> Self {
+        let size = backing.len();
+        let pieces = if size > 0 {
+            vec![Piece { source: Source::Original, offset: 0, len: size }]
+        } else {
+            Vec::new()
+        };
+
+        let mut buffer = Self {
+            backing,
+            original_len: size,
+            added: Added::default(),
+            pieces,
+            starts: Vec::new(),
+            len: size,
+            saved: Vec::new(),
+            saved_ranges: Vec::new(),
+            original_on_disk: true,
+            error: RefCell::new(None),
+        };
+        buffer.update_starts();
+        buffer.mark_saved();
+
+        buffer
+    }
+
+    // A read-only copy of the current contents that can be moved to another
+    // thread. Saving it is not supported.
+    pub fn snapshot(&self) -> Result<Buffer> {
+        Ok(Buffer {
+            backing:          self.backing.try_clone()?,
+            original_len:     self.original_len,
+            added:            self.added.clone(),
+            pieces:           self.pieces.clone(),
+            starts:           self.starts.clone(),
+            len:              self.len,
+            saved:            Vec::new(),
+            saved_ranges:     Vec::new(),
+            original_on_disk: false,
+            error:            RefCell::new(None),
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn backing(&self) -> &Backing {
+        &self.backing
+    }
+
+    // Devices and process memory have a fixed size.
+    #[inline]
+    pub fn can_resize(&self) -> bool {
+        self.backing.can_resize()
+    }
+
+    // Whether the ranges can be replaced with len bytes each.
+    pub fn can_replace(&self, ranges: &[(u64, u64)], len: u64) -> bool {
+        self.can_resize() || ranges.iter().all(|(start_offset, end_offset)| end_offset - start_offset == len)
+    }
+
+    #[inline]
+    pub fn is_growing(&self) -> bool {
+        self.backing.is_growing()
+    }
+
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.error.borrow_mut().take().or_else(|| self.backing.take_error())
+    }
+
+    // Appends data that arrived in the backing since the last call.
+    pub fn refresh(&mut self) -> bool {
+        let size = self.backing.len();
+        if size <= self.original_len {
+            return false;
+        }
+
+        let piece = Piece { source: Source::Original, offset: self.original_len, len: size - self.original_len };
+        push_piece(&mut self.pieces, piece);
+        push_piece(&mut self.saved, piece);
+        self.original_len = size;
+        self.len += piece.len;
+        self.update_starts();
+        self.update_saved_ranges();
+
+        true
+    }
+
+    // Follows changes of the file size on disk. Data that got cut off reads
+    // as an error from then on.
+    pub fn check_file(&mut self, path: &Path) -> Result<FileChange> {
+        if !self.original_on_disk {
+            // Saved into a new file, the old one is what we have mapped. It
+            // only matters if that one got truncated anyway.
+            return Ok(if self.backing.is_faulted() { FileChange::Replaced } else { FileChange::Unchanged });
+        }
+
+        let change = self.backing.check(path)?;
+        match change {
+            FileChange::Grew => {
+                self.refresh();
+            }
+            FileChange::Shrank => {
+                let size = self.backing.len();
+                if !self.is_modified() {
+                    self.pieces.clear();
+                    if size > 0 {
+                        self.pieces.push(Piece { source: Source::Original, offset: 0, len: size });
+                    }
+                    self.len = size;
+                    self.update_starts();
+                    self.mark_saved();
+                }
+                self.original_len = min(self.original_len, size);
+            }
+            FileChange::Unchanged | FileChange::Replaced => {}
+        }
+
+        Ok(change)
+    }
+
+    // Remaps the open file after it got truncated, even if it isn't the one at
+    // the path anymore. The pieces are kept, data that got cut off reads as an
+    // error.
+    pub fn remap(&mut self) -> Result<FileChange> {
+        let change = self.backing.remap()?;
+        self.original_len = min(self.original_len, self.backing.len());
+        Ok(change)
+    }
+
+    // Whether the pieces refer to data that got cut off the file.
+    pub fn is_truncated(&self) -> bool {
+        let size = self.backing.len();
+        self.pieces.iter().any(|piece| piece.source == Source::Original && piece.end_offset() > size)
+    }
+
+    fn read_piece(&self, source: Source, offset: u64, buf: &mut [u8]) {
+        match source {
+            Source::Original => {
+                if let Err(error) = self.backing.read(offset, buf) {
+                    buf.fill(0);
+                    *self.error.borrow_mut() = Some(error);
+                }
+            }
+            Source::Added => {
+                self.added.read(offset, buf);
+            }
+        }
+    }
+
+    // index of the piece containing offset, pieces.len() if offset is at the end
+    fn index_of(&self, offset: u64) -> usize {
+        if offset >= self.len {
+            return self.pieces.len();
+        }
+        self.starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    fn update_starts(&mut self) {
+        self.starts.clear();
+        let mut offset = 0;
+        for piece in &self.pieces {
+            self.starts.push(offset);
+            offset += piece.len;
+        }
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let end_offset = min(offset.saturating_add(buf.len() as u64), self.len);
+        if offset >= end_offset {
+            return 0;
+        }
+
+        let mut index = self.index_of(offset);
+        let mut pos = offset;
+        while pos < end_offset {
+            let piece = &self.pieces[index];
+            let skip  = pos - self.starts[index];
+            let count = min(piece.len - skip, end_offset - pos);
+            let buf_offset = (pos - offset) as usize;
+            self.read_piece(piece.source, piece.offset + skip, &mut buf[buf_offset..buf_offset + count as usize]);
+            pos   += count;
+            index += 1;
+        }
+
+        (end_offset - offset) as usize
+    }
+
+    pub fn read_range(&self, start_offset: u64, end_offset: u64) -> Vec<u8> {
+        let end_offset = min(end_offset, self.len);
+        let mut data = vec![0; end_offset.saturating_sub(start_offset) as usize];
+        self.read(start_offset, &mut data);
+        data
+    }
+
+    pub fn get(&self, offset: u64) -> Option<u8> {
+        let mut buf = [0];
+        if self.read(offset, &mut buf) == 1 {
+            Some(buf[0])
+        } else {
+            None
+        }
+    }
+
+    pub fn write_range(&self, start_offset: u64, end_offset: u64, writer: &mut impl Write) -> std::io::Result<()> {
+        let end_offset = min(end_offset, self.len);
+        let mut buf = vec![0; min(BLOCK_SIZE as u64, end_offset.saturating_sub(start_offset)) as usize];
+        let mut offset = start_offset;
+        while offset < end_offset {
+            let count = min(buf.len() as u64, end_offset - offset) as usize;
+            self.read(offset, &mut buf[..count]);
+            if let Some(error) = self.error.borrow_mut().take() {
+                return Err(error);
+            }
+            writer.write_all(&buf[..count])?;
+            offset += count as u64;
+        }
+        Ok(())
+    }
+
+    // Makes sure a piece starts at offset and returns its index.
+    fn split_at(&mut self, offset: u64) -> usize {
+        let index = self.index_of(offset);
+        if index < self.pieces.len() {
+            let start = self.starts[index];
+            if start < offset {
+                let piece = self.pieces[index];
+                let head  = offset - start;
+                self.pieces[index].len = head;
+                self.pieces.insert(index + 1, piece.skip(head));
+                self.starts.insert(index + 1, offset);
+                return index + 1;
+            }
+        }
+        index
+    }
+
+    fn coalesce(&mut self) {
+        let mut index = 0;
+        while index < self.pieces.len() {
+            if self.pieces[index].len == 0 {
+                self.pieces.remove(index);
+            } else if index > 0 {
+                let prev = self.pieces[index - 1];
+                let piece = self.pieces[index];
+                if prev.source == piece.source && prev.end_offset() == piece.offset {
+                    self.pieces[index - 1].len += piece.len;
+                    self.pieces.remove(index);
+                } else {
+                    index += 1;
+                }
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    // Replace len bytes at offset with the given pieces and return the
+    // removed pieces.
+    pub fn splice(&mut self, offset: u64, len: u64, pieces: &[Piece]) -> Vec<Piece> {
+        let start_index = self.split_at(offset);
+        let end_index   = self.split_at(offset + len);
+
+        let removed: Vec<Piece> = self.pieces.splice(start_index..end_index, pieces.iter().copied()).collect();
+
+        self.len = self.len - len + pieces_len(pieces);
+        self.coalesce();
+        self.update_starts();
+
+        removed
+    }
+
+    pub fn replace(&mut self, offset: u64, len: u64, data: &[u8]) -> Change {
+        let mut new = Vec::new();
+        if !data.is_empty() {
+            new.push(Piece {
+                source: Source::Added,
+                offset: self.added.len(),
+                len:    data.len() as u64,
+            });
+            self.added.extend_from_slice(data);
+        }
+        let old = self.splice(offset, len, &new);
+
+        Change { offset, old, new }
+    }
+
+    // Replaces all the given sorted, non-overlapping ranges with data as a
+    // single change. The replacement is stored only once.
+    pub fn replace_ranges(&mut self, ranges: &[(u64, u64)], data: &[u8]) -> Change {
+        let start_offset = ranges.first().map(|range| range.0).unwrap_or(0);
+        let end_offset   = ranges.last().map(|range| range.1).unwrap_or(0);
+
+        let replacement = Piece {
+            source: Source::Added,
+            offset: self.added.len(),
+            len:    data.len() as u64,
+        };
+        self.added.extend_from_slice(data);
+
+        let mut new = Vec::new();
+        let mut offset = start_offset;
+        for &(range_start, range_end) in ranges {
+            self.push_pieces(&mut new, offset, range_start);
+            if replacement.len > 0 {
+                new.push(replacement);
+            }
+            offset = range_end;
+        }
+        let old = self.splice(start_offset, end_offset - start_offset, &new);
+
+        Change { offset: start_offset, old, new }
+    }
+
+    fn push_pieces(&self, pieces: &mut Vec<Piece>, start_offset: u64, end_offset: u64) {
+        let mut index = self.index_of(start_offset);
+        let mut pos = start_offset;
+        while pos < end_offset {
+            let piece = &self.pieces[index];
+            let skip  = pos - self.starts[index];
+            let count = min(piece.len - skip, end_offset - pos);
+            push_piece(pieces, Piece { source: piece.source, offset: piece.offset + skip, len: count });
+            pos   += count;
+            index += 1;
+        }
+    }
+
+    #[inline]
+    pub fn undo(&mut self, change: &Change) {
+        self.splice(change.offset, change.new_len(), &change.old);
+    }
+
+    #[inline]
+    pub fn redo(&mut self, change: &Change) {
+        self.splice(change.offset, change.old_len(), &change.new);
+    }
+
+    #[inline]
+    pub fn is_modified(&self) -> bool {
+        self.pieces != self.saved
+    }
+
+    // Byte ranges in start_offset..end_offset whose data isn't part of the
+    // file on disk (data that only got shifted around isn't considered
+    // modified).
+    pub fn modified_ranges(&self, start_offset: u64, end_offset: u64) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let end_offset = min(end_offset, self.len);
+        if start_offset >= end_offset {
+            return ranges;
+        }
+
+        let mut offset = start_offset;
+        let mut index  = self.index_of(start_offset);
+        while offset < end_offset {
+            let piece = self.pieces[index];
+            let skip  = offset - self.starts[index];
+            let count = min(piece.len - skip, end_offset - offset);
+            let piece = Piece { source: piece.source, offset: piece.offset + skip, len: count };
+
+            // gaps in saved_ranges are modified
+            let mut source_offset = piece.offset;
+            let mut range_index = self.saved_ranges.partition_point(|range|
+                (range.source, range.end_offset()) <= (piece.source, piece.offset));
+            while source_offset < piece.end_offset() {
+                let next_offset = if let Some(range) = self.saved_ranges.get(range_index).filter(|range|
+                        range.source == piece.source && range.offset < piece.end_offset()) {
+                    if range.offset > source_offset {
+                        range.offset
+                    } else {
+                        source_offset = min(range.end_offset(), piece.end_offset());
+                        range_index += 1;
+                        continue;
+                    }
+                } else {
+                    piece.end_offset()
+                };
+
+                let start = offset + source_offset - piece.offset;
+                let end   = offset + next_offset   - piece.offset;
+                push_range(&mut ranges, start, end);
+                source_offset = next_offset;
+            }
+
+            offset += count;
+            index  += 1;
+        }
+
+        ranges
+    }
+
+    // Byte ranges that differ from the file on disk at the same position.
+    fn changed_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut pieces = self.pieces.iter().copied();
+        let mut saved  = self.saved.iter().copied();
+        let mut piece  = pieces.next();
+        let mut saved_piece = saved.next();
+        let mut offset = 0;
+
+        while let (Some(current), Some(on_disk)) = (piece, saved_piece) {
+            let count = min(current.len, on_disk.len);
+            if current.source != on_disk.source || current.offset != on_disk.offset {
+                push_range(&mut ranges, offset, offset + count);
+            }
+            offset += count;
+            piece       = if current.len > count { Some(current.skip(count)) } else { pieces.next() };
+            saved_piece = if on_disk.len > count { Some(on_disk.skip(count)) } else { saved.next() };
+        }
+
+        ranges
+    }
+
+    fn mark_saved(&mut self) {
+        self.saved = self.pieces.clone();
+        self.update_saved_ranges();
+    }
+
+    fn update_saved_ranges(&mut self) {
+        let mut ranges = self.saved.clone();
+        ranges.sort_by_key(|piece| (piece.source, piece.offset));
+        self.saved_ranges.clear();
+        for range in ranges {
+            if let Some(last) = self.saved_ranges.last_mut() {
+                if last.source == range.source && last.end_offset() >= range.offset {
+                    last.len = std::cmp::max(last.end_offset(), range.end_offset()) - last.offset;
+                    continue;
+                }
+            }
+            self.saved_ranges.push(range);
+        }
+    }
+
+    pub fn save(&mut self, path: &Path, journal: &mut Journal) -> Result<()> {
+        if !self.is_modified() {
+            return Ok(());
+        }
+
+        self.backing.file()?;
+
+        if self.is_truncated() {
+            // the missing data would be written as zeros
+            return Err(Error::message("the file shrank and the changes refer to data that is gone"));
+        }
+
+        let same_len = self.len == pieces_len(&self.saved);
+        if same_len && (!self.original_on_disk || self.is_overwrite_only()) {
+            self.save_in_place(path, journal)?;
+        } else if std::fs::metadata(path)?.is_file() {
+            self.save_as_copy(path)?;
+        } else if same_len {
+            // Devices can't be replaced, so data that moved has to be copied
+            // into memory before it gets overwritten.
+            self.save_in_place(path, journal)?;
+        } else {
+            return Err(Error::message("cannot change the size of a device"));
+        }
+
+        self.mark_saved();
+
+        Ok(())
+    }
+
+    // Whether all data of the original file is still at its offset, i.e. the
+    // changes can be written in place by only copying the overwritten bytes.
+    fn is_overwrite_only(&self) -> bool {
+        self.pieces.iter().zip(&self.starts).all(|(piece, start)|
+            piece.source == Source::Added || piece.offset == *start)
+    }
+
+    fn save_in_place(&mut self, path: &Path, journal: &mut Journal) -> Result<()> {
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        let ranges = self.changed_ranges();
+
+        if self.original_on_disk {
+            let meta = file.metadata()?;
+            let orig_meta = self.backing.file()?.metadata()?;
+            self.original_on_disk = meta.dev() == orig_meta.dev() && meta.ino() == orig_meta.ino();
+        }
+
+        if self.original_on_disk {
+            // The mapping will see the new data, so everything that still
+            // needs the old data of these ranges gets a copy of it. Each
+            // range is copied at most once and shared by all references.
+            let mut copies = vec![None; ranges.len()];
+            detach(&self.backing, &mut self.added, &mut copies, &mut self.pieces, &ranges)?;
+            for pieces in journal.pieces_mut() {
+                detach(&self.backing, &mut self.added, &mut copies, pieces, &ranges)?;
+            }
+        }
+
+        let mut buf = vec![0; BLOCK_SIZE];
+        for (start_offset, end_offset) in ranges {
+            let mut offset = start_offset;
+            while offset < end_offset {
+                let count = self.read(offset, &mut buf[..min(BLOCK_SIZE as u64, end_offset - offset) as usize]);
+                if let Some(error) = self.error.borrow_mut().take() {
+                    return Err(error.into());
+                }
+                file.write_all_at(&buf[..count], offset)?;
+                offset += count as u64;
+            }
+        }
+        file.sync_data()?;
+        self.backing.invalidate();
+
+        if self.original_on_disk && self.len > 0 && self.original_len == self.len {
+            // the mapping now is identical to the buffer again
+            self.pieces.clear();
+            self.pieces.push(Piece { source: Source::Original, offset: 0, len: self.len });
+            self.update_starts();
+        }
+
+        Ok(())
+    }
+
+    // Write everything into a new file and move it over the old one. The
+    // old file stays mapped, so all pieces stay valid.
+    fn save_as_copy(&mut self, path: &Path) -> Result<()> {
+        let file_name = path.file_name().ok_or_else(|| Error::message("not a file"))?;
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(".hox-tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let result = (|| -> std::io::Result<()> {
+            let file = std::fs::OpenOptions::new().write(true).create_new(true).open(&tmp_path)?;
+            file.set_permissions(std::fs::metadata(path)?.permissions())?;
+
+            let mut writer = std::io::BufWriter::with_capacity(BLOCK_SIZE, file);
+            self.write_range(0, self.len, &mut writer)?;
+            let file = writer.into_inner().map_err(|error| error.into_error())?;
+            file.sync_all()?;
+
+            std::fs::rename(&tmp_path, path)
+        })();
+
+        if let Err(error) = result {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(error.into());
+        }
+
+        self.original_on_disk = false;
+
+        Ok(())
+    }
+}
+
+fn push_piece(pieces: &mut Vec<Piece>, piece: Piece) {
+    if let Some(last) = pieces.last_mut() {
+        if last.source == piece.source && last.end_offset() == piece.offset {
+            last.len += piece.len;
+            return;
+        }
+    }
+    pieces.push(piece);
+}
+
+fn push_range(ranges: &mut Vec<(u64, u64)>, start_offset: u64, end_offset: u64) {
+    if let Some(last) = ranges.last_mut() {
+        if last.1 == start_offset {
+            last.1 = end_offset;
+            return;
+        }
+    }
+    ranges.push((start_offset, end_offset));
+}
+
+// Replace references to the given ranges of the original file with copies.
+// copies holds the offset in added of the copy of each range, if made yet.
+fn detach(backing: &Backing, added: &mut Added, copies: &mut [Option<u64>], pieces: &mut Vec<Piece>, ranges: &[(u64, u64)]) -> std::io::Result<()> {
+    let overlaps = |piece: &Piece| {
+        let index = ranges.partition_point(|(_, end_offset)| *end_offset <= piece.offset);
+        piece.source == Source::Original && index < ranges.len() && ranges[index].0 < piece.end_offset()
+    };
+
+    if !pieces.iter().any(overlaps) {
+        return Ok(());
+    }
+
+    let mut result = Vec::with_capacity(pieces.len());
+    for piece in pieces.iter().copied() {
+        if !overlaps(&piece) {
+            result.push(piece);
+            continue;
+        }
+
+        let mut offset = piece.offset;
+        let mut index  = ranges.partition_point(|(_, end_offset)| *end_offset <= offset);
+        while offset < piece.end_offset() {
+            if index < ranges.len() && ranges[index].0 < piece.end_offset() {
+                let (start_offset, end_offset) = ranges[index];
+                if start_offset > offset {
+                    result.push(Piece { source: Source::Original, offset, len: start_offset - offset });
+                    offset = start_offset;
+                }
+                let copy_offset = if let Some(copy_offset) = copies[index] {
+                    copy_offset
+                } else {
+                    let copy_offset = added.len();
+                    let mut read_offset = start_offset;
+                    added.extend_with(end_offset - start_offset, |buf| {
+                        backing.read(read_offset, buf)?;
+                        read_offset += buf.len() as u64;
+                        Ok(())
+                    })?;
+                    copies[index] = Some(copy_offset);
+                    copy_offset
+                };
+                let end_offset = min(end_offset, piece.end_offset());
+                result.push(Piece { source: Source::Added, offset: copy_offset + offset - start_offset, len: end_offset - offset });
+                offset = end_offset;
+                index += 1;
+            } else {
+                result.push(Piece { source: Source::Original, offset, len: piece.end_offset() - offset });
+                offset = piece.end_offset();
+            }
+        }
+    }
+
+    *pieces = result;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::Location;
+
+    const LOCATION: Location = Location { cursor: 0, selection_start: 0, selection_end: 0 };
+
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("hox-test-{}-{}", std::process::id(), name));
+            std::fs::write(&path, data).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn open(path: &Path) -> Buffer {
+        Buffer::new(Backing::open(std::fs::File::open(path).unwrap()).unwrap())
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|index| (index % 251) as u8).collect()
+    }
+
+    fn contents(buffer: &Buffer) -> Vec<u8> {
+        buffer.read_range(0, buffer.len())
+    }
+
+    fn undo(buffer: &mut Buffer, journal: &mut Journal) {
+        for change in journal.undo().unwrap().changes.iter().rev() {
+            buffer.undo(change);
+        }
+    }
+
+    fn redo(buffer: &mut Buffer, journal: &mut Journal) {
+        for change in &journal.redo().unwrap().changes {
+            buffer.redo(change);
+        }
+    }
+
+    #[test]
+    fn save_in_place_copies_each_range_once() {
+        let orig = data(4096);
+        let file = TempFile::new("copy-once", &orig);
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+
+        // shift everything by one byte and undo an overwrite, so the buffer
+        // and the redo step both refer to bytes 10..12 of the file
+        journal.record(buffer.replace(0, 1, b""), LOCATION);
+        journal.close_step();
+        journal.record(buffer.replace(buffer.len(), 0, b"x"), LOCATION);
+        journal.close_step();
+        journal.record(buffer.replace(9, 2, b"ab"), LOCATION);
+        undo(&mut buffer, &mut journal);
+        let shifted = contents(&buffer);
+
+        buffer.save_in_place(&file.0, &mut journal).unwrap();
+        assert_eq!(std::fs::read(&file.0).unwrap(), shifted);
+        assert_eq!(contents(&buffer), shifted);
+        assert_eq!(buffer.added.len(), 3 + orig.len() as u64);
+
+        redo(&mut buffer, &mut journal);
+        let mut expected = shifted.clone();
+        expected[9..11].copy_from_slice(b"ab");
+        assert_eq!(contents(&buffer), expected);
+
+        for _ in 0..3 {
+            undo(&mut buffer, &mut journal);
+        }
+        assert_eq!(contents(&buffer), orig);
+    }
+
+    #[test]
+    fn added_is_shared_with_snapshots() {
+        let orig = data(BLOCK_SIZE + BLOCK_SIZE / 2);
+        let mut added = Added::default();
+        added.extend_from_slice(&orig[..10]);
+        added.extend_from_slice(&orig[10..]);
+        assert_eq!(added.len(), orig.len() as u64);
+
+        let mut buf = vec![0; 100];
+        added.read(BLOCK_SIZE as u64 - 50, &mut buf);
+        assert_eq!(buf, orig[BLOCK_SIZE - 50..BLOCK_SIZE + 50]);
+
+        let snapshot = added.clone();
+        added.extend_from_slice(b"xyz");
+        assert!(Arc::ptr_eq(&added.chunks[0], &snapshot.chunks[0]));
+        assert_eq!(snapshot.len(), orig.len() as u64);
+
+        let mut buf = vec![0; 3];
+        added.read(orig.len() as u64, &mut buf);
+        assert_eq!(buf, b"xyz");
+    }
+
+    #[test]
+    fn save_overwrite_in_place() {
+        let orig = data(4096);
+        let file = TempFile::new("overwrite", &orig);
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+        let ino = std::fs::metadata(&file.0).unwrap().ino();
+
+        journal.record(buffer.replace(10, 2, b"ab"), LOCATION);
+        buffer.save(&file.0, &mut journal).unwrap();
+
+        let mut expected = orig.clone();
+        expected[10..12].copy_from_slice(b"ab");
+        assert_eq!(std::fs::read(&file.0).unwrap(), expected);
+        assert_eq!(std::fs::metadata(&file.0).unwrap().ino(), ino);
+        assert_eq!(contents(&buffer), expected);
+
+        // only the overwritten bytes got copied for the undo journal
+        assert_eq!(buffer.added.len(), 4);
+
+        // undo still sees the old bytes even though the mapping changed
+        undo(&mut buffer, &mut journal);
+        assert_eq!(contents(&buffer), orig);
+    }
+
+    #[test]
+    fn save_shifted_as_copy() {
+        let orig = data(1024 * 1024);
+        let file = TempFile::new("shifted", &orig);
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+
+        journal.record(buffer.replace(0, 1, b""), LOCATION);
+        journal.record(buffer.replace(buffer.len(), 0, b"x"), LOCATION);
+        buffer.save(&file.0, &mut journal).unwrap();
+
+        let mut expected = orig[1..].to_vec();
+        expected.push(b'x');
+        assert_eq!(std::fs::read(&file.0).unwrap(), expected);
+        assert_eq!(contents(&buffer), expected);
+
+        // nothing of the file got copied into memory
+        assert_eq!(buffer.added.len(), 1);
+    }
+
+    #[test]
+    fn check_file_grow_and_shrink() {
+        let orig = data(100);
+        let file = TempFile::new("check-file", &orig);
+        let mut buffer = open(&file.0);
+        let mut handle = std::fs::OpenOptions::new().append(true).open(&file.0).unwrap();
+
+        handle.write_all(b"more").unwrap();
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Grew);
+        let mut expected = orig.clone();
+        expected.extend_from_slice(b"more");
+        assert_eq!(contents(&buffer), expected);
+        assert!(!buffer.is_modified());
+
+        handle.set_len(50).unwrap();
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Shrank);
+        assert_eq!(contents(&buffer), orig[..50]);
+        assert!(!buffer.is_modified());
+        assert!(!buffer.is_truncated());
+    }
+
+    #[test]
+    fn shrink_with_changes_refuses_to_save() {
+        let orig = data(100);
+        let file = TempFile::new("check-file-modified", &orig);
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+        let handle = std::fs::OpenOptions::new().write(true).open(&file.0).unwrap();
+
+        journal.record(buffer.replace(0, 1, b"x"), LOCATION);
+        handle.set_len(50).unwrap();
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Shrank);
+        assert!(buffer.is_truncated());
+        assert_eq!(buffer.len(), 100);
+
+        // the data that is gone reads as zeros and an error
+        assert_eq!(buffer.read_range(50, 100), vec![0; 50]);
+        assert!(buffer.take_error().is_some());
+
+        assert!(buffer.save(&file.0, &mut journal).is_err());
+        assert_eq!(std::fs::metadata(&file.0).unwrap().len(), 50);
+
+        // nothing refers to the missing data after deleting it
+        journal.record(buffer.replace(50, 50, b""), LOCATION);
+        assert!(!buffer.is_truncated());
+        buffer.save(&file.0, &mut journal).unwrap();
+        let mut expected = orig[..50].to_vec();
+        expected[0] = b'x';
+        assert_eq!(std::fs::read(&file.0).unwrap(), expected);
+    }
+
+    #[test]
+    fn fault_after_save_as_copy() {
+        let orig = data(4096);
+        let file = TempFile::new("fault-after-copy", &orig);
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+        let old_file = std::fs::OpenOptions::new().write(true).open(&file.0).unwrap();
+
+        journal.record(buffer.replace(0, 1, b""), LOCATION);
+        buffer.save(&file.0, &mut journal).unwrap();
+        assert!(!buffer.original_on_disk);
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Unchanged);
+
+        // the replaced file that is still mapped gets truncated
+        old_file.set_len(0).unwrap();
+        assert_eq!(buffer.read_range(0, 16), vec![0; 16]);
+        assert!(buffer.take_error().is_some());
+        assert!(buffer.backing().is_faulted());
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Replaced);
+
+        assert_eq!(buffer.remap().unwrap(), FileChange::Shrank);
+        assert!(!buffer.backing().is_faulted());
+        assert!(buffer.is_truncated());
+        assert_eq!(buffer.check_file(&file.0).unwrap(), FileChange::Unchanged);
+    }
+
+    fn memory(data: &[u8]) -> Buffer {
+        let mut buffer = Buffer::new(Backing::Empty(std::fs::File::open("/dev/null").unwrap()));
+        buffer.replace(0, 0, data);
+        buffer.mark_saved();
+        buffer
+    }
+
+    #[test]
+    fn replace_all_shorter_and_longer() {
+        let mut buffer = memory(b"one two one three one");
+        let ranges = [(0, 3), (8, 11), (18, 21)];
+
+        let change = buffer.replace_ranges(&ranges, b"1");
+        assert_eq!(contents(&buffer), b"1 two 1 three 1");
+        buffer.undo(&change);
+        assert_eq!(contents(&buffer), b"one two one three one");
+
+        let change = buffer.replace_ranges(&ranges, b"eleven");
+        assert_eq!(contents(&buffer), b"eleven two eleven three eleven");
+        buffer.undo(&change);
+        buffer.redo(&change);
+        assert_eq!(contents(&buffer), b"eleven two eleven three eleven");
+
+        let change = buffer.replace_ranges(&[(0, 7), (10, 11)], b"");
+        assert_eq!(contents(&buffer), b"twoeleven three eleven");
+        buffer.undo(&change);
+        assert_eq!(contents(&buffer), b"eleven two eleven three eleven");
+    }
+
+    #[test]
+    fn replace_all_is_one_undo_step() {
+        let file = TempFile::new("replace-all", b"a-b-c-d-e");
+        let mut buffer  = open(&file.0);
+        let mut journal = Journal::new();
+
+        journal.record(buffer.replace(0, 1, b"A"), LOCATION);
+        journal.close_step();
+        journal.record(buffer.replace_ranges(&[(1, 2), (3, 4), (5, 6), (7, 8)], b"+-"), LOCATION);
+        journal.close_step();
+        assert_eq!(contents(&buffer), b"A+-b+-c+-d+-e");
+
+        undo(&mut buffer, &mut journal);
+        assert_eq!(contents(&buffer), b"A-b-c-d-e");
+        undo(&mut buffer, &mut journal);
+        assert_eq!(contents(&buffer), b"a-b-c-d-e");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn replace_size_only_changes_if_possible() {
+        let ranges = [(0, 2), (4, 6)];
+
+        // /dev/null is a device
+        let buffer = memory(b"abcdef");
+        assert!(!buffer.can_resize());
+        assert!(buffer.can_replace(&ranges, 2));
+        assert!(!buffer.can_replace(&ranges, 1));
+        assert!(!buffer.can_replace(&ranges, 3));
+        assert!(!buffer.can_replace(&[(0, 2), (3, 4)], 2));
+        assert!(buffer.can_replace(&[], 1));
+
+        let file = TempFile::new("can-replace", b"abcdef");
+        let buffer = open(&file.0);
+        assert!(buffer.can_resize());
+        assert!(buffer.can_replace(&ranges, 1));
+        assert!(buffer.can_replace(&ranges, 3));
+    }
+}
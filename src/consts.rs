@@ -15,7 +15,9 @@
 
 #![allow(unused)]
 
+pub const END_OF_TEXT:         char = '\u{3}';  // Ctrl+C
 pub const END_OF_TRANSMISSION: char = '\u{4}';  // Ctrl+D
+pub const DEVICE_CONTROL2:     char = '\u{12}'; // Ctrl+R
 pub const DEVICE_CONTROL3:     char = '\u{13}'; // Ctrl+End
 pub const CANCEL:              char = '\u{18}'; // Ctrl+Home
 pub const END_OF_MEDIUM:       char = '\u{19}'; // Shift+F5
@@ -36,5 +38,6 @@ pub const PAIR_AUTO_COMPLETE:       u8 = 10;
 pub const PAIR_ERROR_MESSAGE:       u8 = 11;
 pub const PAIR_SEARCH_MATCH:        u8 = 12;
 pub const PAIR_SEARCH_MATCH_CURSOR: u8 = 13;
+pub const PAIR_MODIFIED:            u8 = 14;
 
 pub const HISTORY_LENGTH: usize = 1024;
@@ -49,6 +49,11 @@ impl FileInput {
         }
     }
 
+    pub fn with_history(mut self, history: Vec<Vec<char>>) -> Self {
+        self.history = history.into();
+        self
+    }
+
     fn draw(&self, window: &mut Window, cursor: usize, buf: &[char], compl: &[char]) -> Result<()> {
         if self.focused {
             if cursor > 0 {
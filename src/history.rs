@@ -0,0 +1,263 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+use crate::result::{Result, Error};
+use crate::consts::HISTORY_LENGTH;
+use crate::search_widget::SearchMode;
+
+// Search and filename history, stored in $XDG_STATE_HOME/hox/history. Every
+// line is one entry, either "search<TAB>mode<TAB>text" or "file<TAB>path",
+// oldest first. Backslash, tab and line breaks in the text are escaped.
+//
+// Entries are written when they are added, merged with what other instances
+// wrote in the meantime. The file is replaced atomically while holding a lock
+// on history.lock, so readers never see a half written file.
+#[derive(Debug, Default)]
+pub struct History {
+    pub searches: Vec<(SearchMode, Vec<char>)>,
+    pub files:    Vec<Vec<char>>,
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(state_home.join("hox").join("history"))
+}
+
+impl History {
+    pub fn load() -> Result<Self> {
+        match history_path() {
+            Some(path) => Self::read(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn add_search(mode: SearchMode, text: &[char]) -> Result<()> {
+        Self::update(|history| {
+            history.searches.retain(|(other_mode, other_text)| *other_mode != mode || other_text != text);
+            history.searches.push((mode, text.to_vec()));
+            truncate_front(&mut history.searches);
+        })
+    }
+
+    pub fn add_file(path: &[char]) -> Result<()> {
+        Self::update(|history| {
+            history.files.retain(|other| other != path);
+            history.files.push(path.to_vec());
+            truncate_front(&mut history.files);
+        })
+    }
+
+    fn read(path: &PathBuf) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(Self::parse(&String::from_utf8_lossy(&data))),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(Error::io_with_path(error, path)),
+        }
+    }
+
+    // Lines that can't be parsed (e.g. written by a newer version) are skipped.
+    fn parse(data: &str) -> Self {
+        let mut history = Self::default();
+        for line in data.lines() {
+            let mut fields = line.splitn(3, '\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some("search"), Some(mode), Some(text)) => {
+                    if let Some(mode) = SearchMode::from_label(mode) {
+                        history.searches.push((mode, unescape(text)));
+                    }
+                }
+                (Some("file"), Some(path), None) => {
+                    history.files.push(unescape(path));
+                }
+                _ => {}
+            }
+        }
+        truncate_front(&mut history.searches);
+        truncate_front(&mut history.files);
+        history
+    }
+
+    fn format(&self) -> String {
+        let mut data = String::new();
+        for (mode, text) in &self.searches {
+            data.push_str("search\t");
+            data.push_str(&mode.to_string());
+            data.push('\t');
+            escape(text, &mut data);
+            data.push('\n');
+        }
+        for path in &self.files {
+            data.push_str("file\t");
+            escape(path, &mut data);
+            data.push('\n');
+        }
+        data
+    }
+
+    fn update(change: impl FnOnce(&mut Self)) -> Result<()> {
+        let path = if let Some(path) = history_path() {
+            path
+        } else {
+            return Err(Error::message("neither XDG_STATE_HOME nor HOME is set"));
+        };
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|error| Error::io_with_path(error, dir))?;
+
+        // the history file itself is replaced, so it can't be the one locked
+        let lock_path = dir.join("history.lock");
+        let lock = OpenOptions::new().create(true).append(true).open(&lock_path)
+            .map_err(|error| Error::io_with_path(error, &lock_path))?;
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(Error::io_with_path(std::io::Error::last_os_error(), &lock_path));
+        }
+
+        let mut history = Self::read(&path)?;
+        change(&mut history);
+
+        let temp_path = dir.join(format!("history.{}.tmp", std::process::id()));
+        let result = std::fs::write(&temp_path, history.format())
+            .and_then(|_| std::fs::rename(&temp_path, &path));
+        if let Err(error) = result {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(Error::io_with_path(error, &path));
+        }
+
+        // closing the lock file releases the lock
+        Ok(())
+    }
+}
+
+fn truncate_front<T>(entries: &mut Vec<T>) {
+    if entries.len() > HISTORY_LENGTH {
+        entries.drain(..entries.len() - HISTORY_LENGTH);
+    }
+}
+
+fn escape(text: &[char], data: &mut String) {
+    for &ch in text {
+        match ch {
+            '\\' => data.push_str("\\\\"),
+            '\t' => data.push_str("\\t"),
+            '\n' => data.push_str("\\n"),
+            '\r' => data.push_str("\\r"),
+            _    => data.push(ch),
+        }
+    }
+}
+
+fn unescape(text: &str) -> Vec<char> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t')  => result.push('\t'),
+            Some('n')  => result.push('\n'),
+            Some('r')  => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hox::Endian;
+    use crate::search_widget::{TextEncoding, Case, IntSize, Sign, FloatSize};
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn all_modes() -> Vec<SearchMode> {
+        let mut modes = vec![SearchMode::Binary, SearchMode::Regex];
+        for encoding in [TextEncoding::Utf8, TextEncoding::Utf16Le, TextEncoding::Utf16Be, TextEncoding::Latin1] {
+            for case in [Case::Sensitive, Case::IgnoreAscii, Case::IgnoreUnicode] {
+                modes.push(SearchMode::String(encoding, case));
+            }
+        }
+        for endian in [Endian::Little, Endian::Big] {
+            for size in [IntSize::I8, IntSize::I16, IntSize::I32, IntSize::I64] {
+                for sign in [Sign::Signed, Sign::Unsigned] {
+                    modes.push(SearchMode::Integer(size, sign, endian));
+                }
+            }
+            for size in [FloatSize::F32, FloatSize::F64] {
+                modes.push(SearchMode::Float(size, endian));
+            }
+        }
+        modes
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for text in ["", "plain", "a\tb", "line\nbreak\r\n", "back\\slash", "\\t is not a tab", "\\"] {
+            let mut escaped = String::new();
+            escape(&chars(text), &mut escaped);
+            assert!(!escaped.contains(['\t', '\n', '\r']), "{:?}", escaped);
+            assert_eq!(unescape(&escaped), chars(text));
+        }
+    }
+
+    #[test]
+    fn unescape_lenient() {
+        assert_eq!(unescape("trailing\\"), chars("trailing\\"));
+        assert_eq!(unescape("\\x"), chars("x"));
+    }
+
+    #[test]
+    fn mode_label_round_trip() {
+        for mode in all_modes() {
+            assert_eq!(SearchMode::from_label(&mode.to_string()), Some(mode), "{}", mode);
+        }
+        for label in ["", "Text", "UTF-8 x", "Int 12 LE", "Float 32", "UInt 8 ME"] {
+            assert_eq!(SearchMode::from_label(label), None, "{:?}", label);
+        }
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let history = History {
+            searches: all_modes().into_iter().map(|mode| (mode, chars("a\tb\\c\nd"))).collect(),
+            files:    vec![chars("/tmp/with\ttab"), chars("back\\slash\r")],
+        };
+        let data = history.format();
+        assert_eq!(data.lines().count(), history.searches.len() + history.files.len());
+
+        let parsed = History::parse(&data);
+        assert_eq!(parsed.searches, history.searches);
+        assert_eq!(parsed.files, history.files);
+    }
+
+    #[test]
+    fn unknown_lines_are_skipped() {
+        let parsed = History::parse("search\tNo Such Mode\tx\nsearch\tRegex\ta.b\nfuture\tstuff\nfile\tpath\nfile\tpath\textra\n");
+        assert_eq!(parsed.searches, vec![(SearchMode::Regex, chars("a.b"))]);
+        assert_eq!(parsed.files, vec![chars("path")]);
+    }
+}
@@ -16,6 +16,9 @@
 use std::fs::File;
 use std::fmt::Write;
 use std::cmp::{min, max};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[allow(unused)]
 use pancurses_result::{
@@ -25,14 +28,21 @@ use pancurses_result::{
     COLOR_MAGENTA, COLOR_RED, COLOR_WHITE, COLOR_YELLOW,
 };
 
-use crate::mmap::MMap;
-use crate::result::{Result, Error};
+use crate::buffer::Buffer;
+use crate::backing::{Backing, FileChange};
+use crate::result::{Result, Error, ErrorType};
 use crate::number_input::NumberInput;
 use crate::file_input::FileInput;
 use crate::text_box::{TextBox, TextBoxResult};
 use crate::search_widget::{SearchWidget, SearchMode};
+use crate::pattern::Pattern;
+use crate::search_job::{SearchJob, SearchResult};
+use crate::history::History;
+use crate::timestamp;
+use crate::inspector::{InspectorField, InspectorEdit};
 use crate::consts::*;
 use crate::input_widget::{InputWidget, WidgetResult};
+use crate::journal::{Journal, Location};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Endian {
@@ -52,12 +62,46 @@ const MASK_HIGHLIGHT:       u8 =  4;
 const MASK_HIGHLIGHT_END:   u8 =  8;
 const MASK_SELECTED:        u8 = 16;
 const MASK_SELECTED_END:    u8 = 32;
+const MASK_MODIFIED:        u8 = 64;
+
+// longer selections aren't searched for in the view
+const MAX_HIGHLIGHT_SIZE: usize = 64 * 1024;
 
 const REL_OFFSET_LABEL: &str = "Relative Offset: ";
 const FILE_INPUT_LABEL: &str = "Filename: ";
 const SEARCH_LABEL: &str = "Search: ";
+const REPLACE_LABEL: &str = "Replace with: ";
+
+const BOTTOM_WIN_HEIGHT: u8 = 16;
+const MAX_VARINT_LEN: usize = 10;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// searches that take longer than this run in the background with progress
+const SEARCH_WAIT: Duration = Duration::from_millis(100);
+
+// find all stops after this many matches
+const MAX_FIND_ALL: usize = 100_000;
+
+// bytes of a match shown as hex and bytes around it shown as text in the list
+const MATCH_PREVIEW: usize = 8;
+const MATCH_CONTEXT: usize = 8;
+
+fn match_count_label(count: usize, more: bool) -> String {
+    format!("{}{} {}", count, if more { "+" } else { "" }, if count == 1 { "match" } else { "matches" })
+}
 
-const BOTTOM_WIN_HEIGHT: u8 = 7;
+// Ranges of the matches (offset, len) to replace. Of overlapping matches only
+// the first is replaced.
+fn replace_ranges(matches: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::with_capacity(matches.len());
+    for (offset, len) in matches {
+        if ranges.last().map(|&(_, end_offset)| offset >= end_offset).unwrap_or(true) {
+            ranges.push((offset, offset + len));
+        }
+    }
+    ranges
+}
 
 #[inline]
 pub fn is_sidebar_ascii(byte: u8) -> bool {
@@ -196,7 +240,170 @@ fn get_i64(mem: &[u8], cursor: usize, endian: Endian) -> Option<i64> {
     }
 }
 
-fn get_f32(mem: &[u8], cursor: usize, endian: Endian) -> Option<f32> {
+fn get_u24(mem: &[u8], cursor: usize, endian: Endian) -> Option<u32> {
+    if cursor + 3 <= mem.len() {
+        let mem = &mem[cursor..cursor + 3];
+        Some(match endian {
+            Endian::Big    => u32::from_be_bytes([0, mem[0], mem[1], mem[2]]),
+            Endian::Little => u32::from_le_bytes([mem[0], mem[1], mem[2], 0]),
+        })
+    } else {
+        None
+    }
+}
+
+fn get_i24(mem: &[u8], cursor: usize, endian: Endian) -> Option<i32> {
+    // shift the sign bit into place and back again to sign extend
+    get_u24(mem, cursor, endian).map(|num| ((num << 8) as i32) >> 8)
+}
+
+fn get_u128(mem: &[u8], cursor: usize, endian: Endian) -> Option<u128> {
+    if cursor + 16 <= mem.len() {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&mem[cursor..cursor + 16]);
+        Some(match endian {
+            Endian::Big    => u128::from_be_bytes(bytes),
+            Endian::Little => u128::from_le_bytes(bytes),
+        })
+    } else {
+        None
+    }
+}
+
+fn get_i128(mem: &[u8], cursor: usize, endian: Endian) -> Option<i128> {
+    get_u128(mem, cursor, endian).map(|num| num as i128)
+}
+
+// Unsigned LEB128 (also used for protobuf varints) and how many bytes it
+// takes. None if it doesn't end within 10 bytes or doesn't fit into 64 bits.
+fn get_uleb128(mem: &[u8], cursor: usize) -> Option<(u64, usize)> {
+    let mut num = 0u64;
+    for (index, &byte) in mem.get(cursor..)?.iter().take(MAX_VARINT_LEN).enumerate() {
+        let bits = (byte & 0x7F) as u64;
+        let shift = 7 * index as u32;
+        if shift == 63 && bits > 1 {
+            return None;
+        }
+        num |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some((num, index + 1));
+        }
+    }
+    None
+}
+
+fn get_sleb128(mem: &[u8], cursor: usize) -> Option<(i64, usize)> {
+    let mut num = 0i64;
+    for (index, &byte) in mem.get(cursor..)?.iter().take(MAX_VARINT_LEN).enumerate() {
+        let bits = (byte & 0x7F) as i64;
+        let shift = 7 * index as u32;
+        // the last byte may only repeat the sign bit
+        if shift == 63 && bits != 0 && bits != 0x7F {
+            return None;
+        }
+        num |= bits << shift;
+        if byte & 0x80 == 0 {
+            if shift + 7 < 64 && byte & 0x40 != 0 {
+                num |= -1i64 << (shift + 7);
+            }
+            return Some((num, index + 1));
+        }
+    }
+    None
+}
+
+// Microsoft GUIDs store the first three fields little endian, RFC 4122 UUIDs
+// big endian.
+fn format_guid(mem: &[u8], cursor: usize, endian: Endian) -> Option<String> {
+    let data1 = get_u32(mem, cursor, endian)?;
+    let data2 = get_u16(mem, cursor + 4, endian)?;
+    let data3 = get_u16(mem, cursor + 6, endian)?;
+    let data4 = mem.get(cursor + 8..cursor + 16)?;
+
+    Some(format!("{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        data1, data2, data3,
+        data4[0], data4[1], data4[2], data4[3], data4[4], data4[5], data4[6], data4[7]))
+}
+
+// Addresses are always in network byte order.
+fn format_ipv4(mem: &[u8], cursor: usize) -> Option<String> {
+    let bytes: [u8; 4] = mem.get(cursor..cursor + 4)?.try_into().ok()?;
+    Some(Ipv4Addr::from(bytes).to_string())
+}
+
+fn format_ipv6(mem: &[u8], cursor: usize) -> Option<String> {
+    let bytes: [u8; 16] = mem.get(cursor..cursor + 16)?.try_into().ok()?;
+    Some(Ipv6Addr::from(bytes).to_string())
+}
+
+fn format_mac(mem: &[u8], cursor: usize) -> Option<String> {
+    let bytes = mem.get(cursor..cursor + 6)?;
+    Some(format!("{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]))
+}
+
+// Row (counted from the bottom), column and width of an editable value of the
+// inspector.
+fn inspector_field_position(field: InspectorField) -> (i32, i32, usize) {
+    match field {
+        InspectorField::Int8    => (13,  9,  6),
+        InspectorField::Int16   => (12,  9,  6),
+        InspectorField::Int24   => (11,  9,  8),
+        InspectorField::Int32   => (13, 25, 20),
+        InspectorField::Int64   => (12, 25, 20),
+        InspectorField::Float32 => (13, 57, 20),
+        InspectorField::Float64 => (12, 57, 20),
+    }
+}
+
+fn structured_values(mem: &[u8], endian: Endian) -> [(&'static str, Option<String>); 4] {
+    [
+        ("GUID:", format_guid(mem, 0, endian)),
+        ("IPv4:", format_ipv4(mem, 0)),
+        ("IPv6:", format_ipv6(mem, 0)),
+        ("MAC:",  format_mac(mem, 0)),
+    ]
+}
+
+// Index of the first value after the one at previous that is available,
+// wrapping around.
+fn next_structured_value(values: &[(&'static str, Option<String>)], previous: Option<usize>) -> Option<usize> {
+    let start = previous.map(|index| index + 1).unwrap_or(0);
+    (start..start + values.len())
+        .map(|index| index % values.len())
+        .find(|&index| values[index].1.is_some())
+}
+
+fn zigzag_decode(num: u64) -> i64 {
+    (num >> 1) as i64 ^ -((num & 1) as i64)
+}
+
+// IEEE 754 half precision, converted exactly to f32.
+fn get_f16(mem: &[u8], cursor: usize, endian: Endian) -> Option<f32> {
+    let bits = get_u16(mem, cursor, endian)?;
+    let sign     = ((bits >> 15) as u32) << 31;
+    let exponent = ((bits >> 10) & 0x1F) as u32;
+    let mantissa = (bits & 0x3FF) as u32;
+
+    let value = if exponent == 0 {
+        // zero or subnormal: mantissa * 2^-24
+        let value = mantissa as f32 / 16777216.0;
+        if sign != 0 { -value } else { value }
+    } else if exponent == 0x1F {
+        f32::from_bits(sign | 0x7F80_0000 | (mantissa << 13))
+    } else {
+        f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13))
+    };
+
+    Some(value)
+}
+
+// bfloat16 is the upper half of an f32.
+fn get_bf16(mem: &[u8], cursor: usize, endian: Endian) -> Option<f32> {
+    get_u16(mem, cursor, endian).map(|bits| f32::from_bits((bits as u32) << 16))
+}
+
+pub fn get_f32(mem: &[u8], cursor: usize, endian: Endian) -> Option<f32> {
     if cursor + 4 <= mem.len() {
         let mem = [mem[cursor], mem[cursor + 1], mem[cursor + 2], mem[cursor + 3]];
         Some(match endian {
@@ -208,7 +415,7 @@ fn get_f32(mem: &[u8], cursor: usize, endian: Endian) -> Option<f32> {
     }
 }
 
-fn get_f64(mem: &[u8], cursor: usize, endian: Endian) -> Option<f64> {
+pub fn get_f64(mem: &[u8], cursor: usize, endian: Endian) -> Option<f64> {
     if cursor + 8 <= mem.len() {
         let mem = [
             mem[cursor    ], mem[cursor + 1], mem[cursor + 2], mem[cursor + 3],
@@ -224,7 +431,7 @@ fn get_f64(mem: &[u8], cursor: usize, endian: Endian) -> Option<f64> {
 }
 
 // TODO: is there a better way?
-fn hex_len(mut num: usize) -> usize {
+fn hex_len(mut num: u64) -> usize {
     if num == 0 {
         return 1;
     }
@@ -240,55 +447,109 @@ fn hex_len(mut num: usize) -> usize {
 }
 
 
-fn set_search_mask(view_mask: &mut [u8], view_offset: usize, mem: &[u8], needle: &[u8], mask_match: u8, mask_end: u8) {
-    let needle_len = needle.len();
-    if needle_len > 0 {
-        let view_size = view_mask.len();
-        let size = mem.len();
-        let start_offset = if view_offset > (needle_len - 1) {
-            view_offset + 1 - needle_len
-        } else {
-            0
-        };
-        let end_offset = view_offset + view_size + needle_len - 1;
-        let end_offset = if end_offset <= size {
-            end_offset
-        } else {
-            size + 1 - needle_len
-        };
+fn set_search_mask(view_mask: &mut [u8], view_offset: u64, buffer: &Buffer, pattern: &Pattern, mask_match: u8, mask_end: u8) {
+    let max_len = pattern.max_len() as u64;
+    let size = buffer.len();
+    if pattern.is_empty() || max_len == 0 || view_offset >= size {
+        return;
+    }
 
-        let view_end_offset = min(view_offset + view_size, size);
-        for offset in start_offset..end_offset {
-            if &mem[offset..offset + needle_len] == needle {
-                let match_offset_start = max(view_offset, offset);
-                let match_offset_end   = min(view_end_offset, offset + needle_len);
+    // read enough around the view to find matches that cross its borders
+    let view_end_offset = min(view_offset + view_mask.len() as u64, size);
+    let start_offset = view_offset.saturating_sub(max_len - 1);
+    let data = buffer.read_range(start_offset, view_end_offset + max_len - 1);
 
-                let first_view_index = match_offset_start - view_offset;
-                let last_view_index  = match_offset_end - view_offset - 1;
+    pattern.for_each_match(&data, |start, end| {
+        let match_offset_start = max(view_offset, start_offset + start as u64);
+        let match_offset_end   = min(view_end_offset, start_offset + end as u64);
+        if match_offset_start >= match_offset_end {
+            return;
+        }
 
-                if first_view_index < last_view_index {
-                    for item in &mut view_mask[first_view_index..last_view_index] {
-                        *item = (*item & !mask_end) | mask_match;
-                    }
-                }
+        let first_view_index = (match_offset_start - view_offset) as usize;
+        let last_view_index  = (match_offset_end - view_offset - 1) as usize;
 
-                if view_mask[last_view_index] & mask_match == 0 {
-                    view_mask[last_view_index] |= mask_end | mask_match;
-                }
+        if first_view_index < last_view_index {
+            for item in &mut view_mask[first_view_index..last_view_index] {
+                *item = (*item & !mask_end) | mask_match;
+            }
+        }
+
+        if view_mask[last_view_index] & mask_match == 0 {
+            view_mask[last_view_index] |= mask_end | mask_match;
+        }
+    });
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ListKind {
+    Regions,
+    Buffers,
+    Matches,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum ReplaceState {
+    // asking what to do with the match under the cursor
+    Prompt,
+    // looking for all matches (in the selection) to replace
+    Searching { in_selection: bool },
+    // asking whether to replace all of these ranges
+    Confirm { ranges: Vec<(u64, u64)>, in_selection: bool },
+}
+
+struct Replacing {
+    data: Vec<u8>,
+    // the replacement as it was entered
+    label: String,
+    state: ReplaceState,
+}
+
+impl Replacing {
+    fn prompt(&self) -> String {
+        match &self.state {
+            ReplaceState::Prompt | ReplaceState::Searching { .. } => {
+                format!("Replace with {}: y replace, n skip, a all, s all in selection, q stop", self.label)
+            }
+            ReplaceState::Confirm { ranges, in_selection } => {
+                format!("Replace {} {}{} with {}? (y/n)",
+                    ranges.len(),
+                    if ranges.len() == 1 { "match" } else { "matches" },
+                    if *in_selection { " in the selection" } else { "" },
+                    self.label)
             }
         }
     }
 }
 
+// Everything that belongs to one open file. The state of the current buffer
+// lives in Hox itself and is swapped in and out when switching buffers.
+struct BufferState {
+    buffer: Buffer,
+    path: PathBuf,
+    journal: Journal,
+    view_offset: u64,
+    cursor: u64,
+    selection_start: u64,
+    selection_end: u64,
+    selecting: bool,
+    search_pattern: Option<Pattern>,
+    match_count: Option<(usize, bool)>,
+    endian: Endian,
+    signed: bool,
+    follow: bool,
+}
+
 pub struct Hox<'a> {
-    mmap: MMap<'a>,
+    buffer: Buffer,
+    path: PathBuf,
     curses:   Curses,
     win_size: Dimension,
-    view_offset:     usize,
+    view_offset:     u64,
     view_size:       usize,
-    cursor:          usize,
-    selection_start: usize,
-    selection_end:   usize,
+    cursor:          u64,
+    selection_start: u64,
+    selection_end:   u64,
     bytes_per_row:   usize,
     offset_hex_len:  usize,
     const_space:     usize,
@@ -299,19 +560,43 @@ pub struct Hox<'a> {
     selecting: bool,
     view_mask: Vec<u8>,
     view_mask_valid: bool,
-    offset_input: NumberInput<usize>,
-    rel_offset_input: NumberInput<isize>,
+    offset_input: NumberInput<u64>,
+    rel_offset_input: NumberInput<i64>,
     file_input: FileInput,
     help_box: TextBox<'a>,
+    list_box: Option<(ListKind, TextBox<'a>)>,
     help_shown: bool,
     error: Option<String>,
+    info: Option<String>,
+    // cursor and index of the structured value last copied into the status line
+    copied: Option<(u64, usize)>,
+    inspector_edit: Option<InspectorEdit>,
+    // the value last edited in the inspector
+    inspector_field: InspectorField,
     search_widget: SearchWidget,
-    search_data: Vec<u8>,
+    replace_widget: SearchWidget,
+    replacing: Option<Replacing>,
+    search_pattern: Option<Pattern>,
+    search_job: Option<SearchJob>,
+    // count of the last find all and whether there were more matches
+    match_count: Option<(usize, bool)>,
+    // the matches shown in the list
+    matches: Vec<(u64, u64)>,
+    editing: bool,
+    edit_ascii: bool,
+    edit_low_nibble: bool,
+    inserting: bool,
+    journal: Journal,
+    quit_requested: bool,
+    loading: bool,
+    follow: bool,
+    // the other open files, None at the index of the current one
+    buffers: Vec<Option<BufferState>>,
+    current: usize,
 }
 
 impl<'a> Hox<'a> {
-    pub fn new(file: &'a mut File, theme: Theme) -> Result<Self> {
-        let meta = file.metadata()?;
+    pub fn new(buffer: Buffer, path: impl AsRef<Path>, theme: Theme) -> Result<Self> {
 
         let mut curses = initscr()?;
 
@@ -322,15 +607,8 @@ impl<'a> Hox<'a> {
         let window = curses.window_mut();
 
         window.read_interpolate_function_keys(true)?;
-        let size = meta.len();
-        if size > std::usize::MAX as u64 {
-            return Err(Error::message(format!("file size too big: {} > {}", size, std::usize::MAX)));
-        }
 
-        let size = size as usize;
-        let mmap = MMap::new(file, 0, size)?;
-
-        let offset_hex_len = hex_len(size);
+        let offset_hex_len = hex_len(buffer.backing().address(buffer.len()));
         let const_space = offset_hex_len + 5;
 
         let colors = curses.color_mut();
@@ -359,6 +637,7 @@ impl<'a> Hox<'a> {
             colors.set_color_pair(PAIR_ERROR_MESSAGE       as i16, COLOR_RED,   white)?;
             colors.set_color_pair(PAIR_SEARCH_MATCH        as i16, COLOR_BLACK,         202).or_else(|_| colors.set_color_pair(PAIR_SEARCH_MATCH        as i16, COLOR_BLACK,  COLOR_YELLOW))?;
             colors.set_color_pair(PAIR_SEARCH_MATCH_CURSOR as i16, COLOR_BLACK,         197).or_else(|_| colors.set_color_pair(PAIR_SEARCH_MATCH_CURSOR as i16, COLOR_BLACK,  COLOR_RED))?;
+            colors.set_color_pair(PAIR_MODIFIED            as i16, 28,          white).or_else(|_| colors.set_color_pair(PAIR_MODIFIED            as i16, COLOR_GREEN,  white))?;
         } else {
             colors.set_color_pair(PAIR_NORMAL              as i16, COLOR_WHITE, COLOR_BLACK)?;
             colors.set_color_pair(PAIR_INVERTED            as i16, COLOR_BLACK, COLOR_WHITE)?;
@@ -373,11 +652,18 @@ impl<'a> Hox<'a> {
             colors.set_color_pair(PAIR_ERROR_MESSAGE       as i16, COLOR_RED,   COLOR_BLACK)?;
             colors.set_color_pair(PAIR_SEARCH_MATCH        as i16, COLOR_BLACK,         202).or_else(|_| colors.set_color_pair(PAIR_SEARCH_MATCH        as i16, COLOR_BLACK,  COLOR_YELLOW))?;
             colors.set_color_pair(PAIR_SEARCH_MATCH_CURSOR as i16, COLOR_BLACK,         197).or_else(|_| colors.set_color_pair(PAIR_SEARCH_MATCH_CURSOR as i16, COLOR_BLACK,  COLOR_RED))?;
+            colors.set_color_pair(PAIR_MODIFIED            as i16, 46,          COLOR_BLACK).or_else(|_| colors.set_color_pair(PAIR_MODIFIED            as i16, COLOR_GREEN,  COLOR_BLACK))?;
         }
         curses.window_mut().set_background(ColorPair(PAIR_NORMAL));
 
+        let (history, history_error) = match History::load() {
+            Ok(history) => (history, None),
+            Err(error) => (History::default(), Some(format!("Error loading history: {}", error))),
+        };
+
         Ok(Self {
-            mmap,
+            buffer,
+            path: path.as_ref().to_path_buf(),
             curses,
             win_size: Dimension::from((0, 0)),
             view_offset: 0,
@@ -397,7 +683,7 @@ impl<'a> Hox<'a> {
             view_mask_valid: false,
             offset_input: NumberInput::new(16),
             rel_offset_input: NumberInput::new(16),
-            file_input: FileInput::new(0),
+            file_input: FileInput::new(0).with_history(history.files),
             help_box: TextBox::new("\
 Hotkeys
 ═══════
@@ -414,26 +700,89 @@ f or F3 ... open search bar (and search for current selection)
 F ......... clear search
 n or P .... find next
 p or N .... find previous
+a ......... list all matches (Enter jumps to the selected one)
+R ......... replace matches of the search
+            (long searches show their progress, Escape or Ctrl+C cancels)
 # ......... select ASCII line under cursor
+v ......... select LEB128 varint under cursor
+c ......... show GUID/IP/MAC under cursor in the status line
+            (press again for the next one)
+I ......... edit a value of the inspector (int 8 to 64, float 32/64)
+r ......... pick memory region (with --pid)
+b ......... list open files
+] or [ .... switch to next/previous file
+t ......... follow file changes (like tail -f)
+E or Ins .. enter edit mode
+W or F2 ... save changes to file
+u ......... undo last change
+U or ^R ... redo last undone change
+
+Edit Mode
+─────────
+0-9 A-F ... overwrite/insert nibble under cursor (hex column)
+any char .. overwrite/insert byte under cursor (text column)
+Tab ....... switch between hex and text column
+Ins ....... toggle between insert and overwrite
+Delete .... delete selection or byte under cursor
+Backspace . delete byte before cursor (insert) or move cursor left
+Escape .... leave edit mode
+(all other global hotkeys that aren't allowed input characters are active)
 
 Search
 ──────
 Enter or F3 ... find (next)
-F5 ............ switch through input modes: Text/Binary/Integer
+F5 ............ switch through input modes: Text/Binary/Regex/Integer/Float
 Shift+F5 ...... switch through input modes in reverse
+F9 ............ toggle searching only in the selection
+F10 ........... toggle continuing at the other end of the file/selection
 Escape ........ close search bar
 
+Inspector
+─────────
+Tab or ↓ ......... edit the next value
+Shift+Tab or ↑ ... edit the previous value
+Enter ............ write the value at the cursor (using endian and signed)
+Escape or q ...... stop editing
+
+Replace
+───────
+Enter .......... start replacing at the cursor (the replacement may be empty)
+F5 ............. switch through input modes: Text/Binary/Integer/Float
+y .............. replace the match under the cursor and find the next one
+n .............. skip to the next match
+a .............. replace all matches (asks first, undone in one step)
+s .............. replace all matches in the selection (asks first)
+q or Escape .... stop replacing
+A replacement of a different length is only possible if the file can change
+its size (not on devices or process memory).
+
+Text Search
+───────────
+F6 ... switch through case: exact/ignore ASCII case (i)/ignore Unicode case (ui)
+F7 ... switch through encodings: UTF-8/UTF-16LE/UTF-16BE/Latin-1
+F8 ... toggle UTF-16 little endian/big endian
+
 Non-Text Search
 ───────────────
 Escape or q ... close search bar
 (all other global hotkeys that aren't allowed input characters are active)
 
+Binary Search
+─────────────
+? ... wildcard for a nibble, e.g. 4D 5A ?? ?? 50 45 or E8 ?F
+
 Integer Search
 ──────────────
 F6 ... switch through integer sizes: 8/16/32/64
 F7 ... toggle signed/unsigned
 F8 ... toggle little endian/big endian
 
+Float Search
+────────────
+Enter a value or value ~ epsilon, e.g. 3.14 ~ 0.001
+F7 ... toggle 32/64 bit
+F8 ... toggle little endian/big endian
+
 Navigation
 ──────────
 ← ↑ ↓ → .......... move cursor
@@ -452,10 +801,30 @@ Ctrl+Home/Ctrl+End might not work in every terminal. If it doesn't for you use 0
 https://github.com/panzi/rust-hox
 © 2021 Mathias Panzenböck", 2, 1,
             ),
+            list_box: None,
             help_shown: false,
-            error: None,
-            search_widget: SearchWidget::new(0),
-            search_data: Vec::new(),
+            error: history_error,
+            info: None,
+            copied: None,
+            inspector_edit: None,
+            inspector_field: InspectorField::Int8,
+            search_widget: SearchWidget::new(0).with_history(history.searches),
+            replace_widget: SearchWidget::replacement(0),
+            replacing: None,
+            search_pattern: None,
+            search_job: None,
+            match_count: None,
+            matches: Vec::new(),
+            editing: false,
+            edit_ascii: false,
+            edit_low_nibble: false,
+            inserting: false,
+            journal: Journal::new(),
+            quit_requested: false,
+            loading: false,
+            follow: false,
+            buffers: vec![None],
+            current: 0,
         })
     }
 
@@ -464,54 +833,77 @@ https://github.com/panzi/rust-hox
         self.need_redraw = true;
     }
 
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow && self.buffer.backing().file().is_ok();
+        self.need_redraw = true;
+    }
+
     pub fn set_endian(&mut self, endian: Endian) {
         self.endian = endian;
         self.need_redraw = true;
     }
 
-    pub fn set_cursor(&mut self, mut cursor: usize) {
-        let size = self.mmap.size();
+    // In insert mode the cursor may be placed after the last byte.
+    fn max_cursor(&self) -> u64 {
+        let size = self.buffer.len();
+        if self.editing && self.inserting && !self.selecting {
+            size
+        } else if size > 0 {
+            size - 1
+        } else {
+            0
+        }
+    }
 
-        if size > 0 {
-            if cursor >= size {
-                cursor = size - 1;
-            }
+    fn clamp_cursor(&mut self) {
+        let max_cursor = self.max_cursor();
+        if self.cursor > max_cursor {
+            self.set_cursor(max_cursor);
+        }
+    }
 
-            if cursor != self.cursor {
-                if self.selecting {
-                    if cursor > self.cursor {
-                        if self.cursor + 1 == self.selection_end {
-                            self.selection_end = cursor + 1;
-                        } else if cursor >= self.selection_end {
-                            self.selection_start = self.selection_end - 1;
-                            self.selection_end   = cursor + 1;
-                        } else {
-                            self.selection_start = cursor;
+    pub fn set_cursor(&mut self, mut cursor: u64) {
+        let max_cursor = self.max_cursor();
 
-                            if self.selection_end <= self.selection_start {
-                                self.selection_end = cursor + 1;
-                            }
-                        }
+        if cursor > max_cursor {
+            cursor = max_cursor;
+        }
+
+        if cursor != self.cursor {
+            if self.selecting {
+                if cursor > self.cursor {
+                    if self.cursor + 1 == self.selection_end {
+                        self.selection_end = cursor + 1;
+                    } else if cursor >= self.selection_end {
+                        self.selection_start = self.selection_end - 1;
+                        self.selection_end   = cursor + 1;
                     } else {
-                        if self.cursor == self.selection_start {
-                            self.selection_start = cursor;
-                        } else if cursor < self.selection_start {
-                            self.selection_end   = self.selection_start + 1;
-                            self.selection_start = cursor;
-                        } else {
+                        self.selection_start = cursor;
+
+                        if self.selection_end <= self.selection_start {
                             self.selection_end = cursor + 1;
+                        }
+                    }
+                } else {
+                    if self.cursor == self.selection_start {
+                        self.selection_start = cursor;
+                    } else if cursor < self.selection_start {
+                        self.selection_end   = self.selection_start + 1;
+                        self.selection_start = cursor;
+                    } else {
+                        self.selection_end = cursor + 1;
 
-                            if self.selection_end <= self.selection_start {
-                                self.selection_start = cursor;
-                            }
+                        if self.selection_end <= self.selection_start {
+                            self.selection_start = cursor;
                         }
                     }
                 }
-
-                self.cursor = cursor;
-                self.need_redraw = true;
-                self.adjust_view();
             }
+
+            self.cursor = cursor;
+            self.edit_low_nibble = false;
+            self.need_redraw = true;
+            self.adjust_view();
         }
     }
 
@@ -535,8 +927,8 @@ https://github.com/panzi/rust-hox
             return Ok(());
         }
 
-        let mem = self.mmap.mem();
-        let size = mem.len();
+        let size = self.buffer.len();
+        let view_size = self.view_size as u64;
 
         if !self.view_mask_valid {
             // TODO: invalidate view_mask in viewer cases
@@ -550,8 +942,9 @@ https://github.com/panzi/rust-hox
             } else {
                 0
             };
-            if mask_selection_start_offset < self.view_size && self.selection_end > self.view_offset {
-                let mask_selection_end_offset = min(self.selection_end - self.view_offset, self.view_size);
+            if mask_selection_start_offset < view_size && self.selection_end > self.view_offset {
+                let mask_selection_start_offset = mask_selection_start_offset as usize;
+                let mask_selection_end_offset = min(self.selection_end - self.view_offset, view_size) as usize;
                 if mask_selection_end_offset > mask_selection_start_offset {
                     for item in &mut self.view_mask[mask_selection_start_offset..mask_selection_end_offset] {
                         *item = MASK_SELECTED;
@@ -560,19 +953,35 @@ https://github.com/panzi/rust-hox
                 }
             }
 
-            set_search_mask(&mut self.view_mask, self.view_offset, &mem, &mem[self.selection_start..self.selection_end], MASK_HIGHLIGHT, MASK_HIGHLIGHT_END);
-            set_search_mask(&mut self.view_mask, self.view_offset, &mem, &self.search_data, MASK_SEARCH, MASK_SEARCH_END);
+            let view_end_offset = min(self.view_offset + view_size, size);
+            for (start_offset, end_offset) in self.buffer.modified_ranges(self.view_offset, view_end_offset) {
+                for item in &mut self.view_mask[(start_offset - self.view_offset) as usize..(end_offset - self.view_offset) as usize] {
+                    *item |= MASK_MODIFIED;
+                }
+            }
+
+            if self.selection_end - self.selection_start <= MAX_HIGHLIGHT_SIZE as u64 {
+                let selection = Pattern::bytes(self.buffer.read_range(self.selection_start, self.selection_end));
+                set_search_mask(&mut self.view_mask, self.view_offset, &self.buffer, &selection, MASK_HIGHLIGHT, MASK_HIGHLIGHT_END);
+            }
+            if let Some(pattern) = &self.search_pattern {
+                set_search_mask(&mut self.view_mask, self.view_offset, &self.buffer, pattern, MASK_SEARCH, MASK_SEARCH_END);
+            }
 
             self.view_mask_valid = true;
         }
 
-        let view_end_offset = min(self.view_offset + self.view_size, size);
+        let view_end_offset = min(self.view_offset + view_size, size);
+        let mem = self.buffer.read_range(self.view_offset, view_end_offset);
+
+        // in insert mode the cursor can be in the row after the last byte
+        let rows_end_offset = min(self.view_offset + view_size, max(size, self.cursor + 1));
 
         let buf = &mut self.buf;
         let mut line = 0;
-        for row_offset in (self.view_offset..view_end_offset).step_by(bytes_per_row) {
+        for row_offset in (self.view_offset..rows_end_offset).step_by(bytes_per_row) {
             buf.clear();
-            write!(buf, "{:01$X}:", row_offset, self.offset_hex_len)?;
+            write!(buf, "{:01$X}:", self.buffer.backing().address(row_offset), self.offset_hex_len)?;
 
             window.move_to((line, 0))?;
             window.turn_on_attributes(ColorPair(PAIR_OFFSETS))?;
@@ -580,21 +989,21 @@ https://github.com/panzi/rust-hox
 
             window.put_str("  ")?;
 
-            let overflow_offset = row_offset + bytes_per_row;
+            let overflow_offset = row_offset + bytes_per_row as u64;
             let end_byte_offset = min(overflow_offset, size);
 
             let mut byte_offset = row_offset;
             if byte_offset < end_byte_offset {
                 loop {
-                    let mask_index = byte_offset - self.view_offset;
+                    let mask_index = (byte_offset - self.view_offset) as usize;
                     let mask = self.view_mask[mask_index];
 
-                    let byte = mem[byte_offset];
+                    let byte = mem[mask_index];
                     buf.clear();
                     write!(buf, "{:02X}", byte)?;
 
                     if byte_offset == self.cursor {
-                        let attrs = if mask & MASK_SELECTED != 0 {
+                        let cursor_attrs = if mask & MASK_SELECTED != 0 {
                             ColorPair(PAIR_SELECTED_CURSOR)
                         } else if mask & MASK_SEARCH != 0 {
                             ColorPair(PAIR_SEARCH_MATCH_CURSOR)
@@ -602,18 +1011,29 @@ https://github.com/panzi/rust-hox
                             ColorPair(PAIR_CURSOR)
                         };
 
-                        window.turn_on_attributes(attrs)?;
-                        window.put_str(&buf)?;
-
                         let attrs = if mask & MASK_SELECTED != 0 {
                             ColorPair(PAIR_SELECTION)
                         } else if mask & MASK_SEARCH != 0 {
                             ColorPair(PAIR_SEARCH_MATCH)
                         } else if mask & MASK_HIGHLIGHT != 0 {
                             ColorPair(PAIR_SELECTION_MATCH)
+                        } else if mask & MASK_MODIFIED != 0 {
+                            ColorPair(PAIR_MODIFIED)
                         } else {
                             ColorPair(PAIR_NORMAL)
                         };
+
+                        if self.editing && !self.edit_ascii && self.edit_low_nibble {
+                            // high nibble is already typed, only mark the low one
+                            window.turn_on_attributes(attrs)?;
+                            window.put_str(&buf[..1])?;
+                            window.turn_on_attributes(cursor_attrs)?;
+                            window.put_str(&buf[1..])?;
+                        } else {
+                            window.turn_on_attributes(cursor_attrs)?;
+                            window.put_str(&buf)?;
+                        }
+
                         window.turn_on_attributes(attrs)?;
                     } else {
                         let attrs = if mask & MASK_SELECTED != 0 {
@@ -622,6 +1042,8 @@ https://github.com/panzi/rust-hox
                             ColorPair(PAIR_SEARCH_MATCH)
                         } else if mask & MASK_HIGHLIGHT != 0 {
                             ColorPair(PAIR_SELECTION_MATCH)
+                        } else if mask & MASK_MODIFIED != 0 {
+                            ColorPair(PAIR_MODIFIED)
                         } else {
                             ColorPair(PAIR_NORMAL)
                         };
@@ -652,17 +1074,24 @@ https://github.com/panzi/rust-hox
                 }
             }
 
-            for _ in end_byte_offset..overflow_offset {
-                window.put_str("   ")?;
+            for byte_offset in end_byte_offset..overflow_offset {
+                if byte_offset == self.cursor {
+                    window.turn_on_attributes(ColorPair(PAIR_CURSOR))?;
+                    window.put_str("  ")?;
+                    window.turn_on_attributes(ColorPair(PAIR_NORMAL))?;
+                    window.put_char(' ')?;
+                } else {
+                    window.put_str("   ")?;
+                }
             }
 
             window.put_char(' ')?;
 
             for byte_offset in row_offset..end_byte_offset {
-                let mask_index = byte_offset - self.view_offset;
+                let mask_index = (byte_offset - self.view_offset) as usize;
                 let mask = self.view_mask[mask_index];
 
-                let byte = mem[byte_offset];
+                let byte = mem[mask_index];
 
                 let attrs = if byte_offset == self.cursor {
                     if mask & MASK_SELECTED != 0 {
@@ -679,6 +1108,8 @@ https://github.com/panzi/rust-hox
                         ColorPair(PAIR_SEARCH_MATCH)
                     } else if mask & MASK_HIGHLIGHT != 0 {
                         ColorPair(PAIR_SELECTION_MATCH)
+                    } else if mask & MASK_MODIFIED != 0 {
+                        ColorPair(PAIR_MODIFIED)
                     } else if is_sidebar_ascii(byte) {
                         ColorPair(PAIR_NORMAL)
                     } else {
@@ -713,9 +1144,16 @@ https://github.com/panzi/rust-hox
                 }
             }
 
+            let mut text_len = (end_byte_offset - row_offset) as usize;
+            if self.cursor == end_byte_offset && end_byte_offset < overflow_offset {
+                window.turn_on_attributes(ColorPair(PAIR_CURSOR))?;
+                window.put_char(' ')?;
+                text_len += 1;
+            }
+
             window.turn_on_attributes(ColorPair(PAIR_NORMAL))?;
 
-            let remaining = self.win_size.columns as usize - (self.offset_hex_len + 2 + 3 * bytes_per_row + 1 + (end_byte_offset - row_offset));
+            let remaining = self.win_size.columns as usize - (self.offset_hex_len + 2 + 3 * bytes_per_row + 1 + text_len);
 
             for _ in 0..remaining {
                 window.put_char(' ')?;
@@ -724,8 +1162,18 @@ https://github.com/panzi/rust-hox
             line += 1;
         }
 
+        // clear rows left over from a longer file
+        let view_rows = self.win_size.rows - BOTTOM_WIN_HEIGHT as i32;
+        while line < view_rows {
+            window.move_to((line, 0))?;
+            for _ in 0..self.win_size.columns {
+                window.put_char(' ')?;
+            }
+            line += 1;
+        }
+
         let rows = self.win_size.rows;
-        window.move_to((rows - 6, 0))?;
+        window.move_to((rows - 15, 0))?;
 
         buf.clear();
         write!(buf, " &Offset: [ {:>14} ]  &Selection: ",
@@ -740,6 +1188,20 @@ https://github.com/panzi/rust-hox
         if self.selecting {
             buf.push_str(" selecting");
         }
+        if self.editing {
+            buf.push_str(if self.edit_ascii { " editing text" } else { " editing hex" });
+            buf.push_str(if self.inserting { " (insert)" } else { " (overwrite)" });
+        }
+        if let Some(memory) = self.buffer.backing().process() {
+            if let Some(index) = memory.region_index(self.cursor) {
+                let region = &memory.regions()[index];
+                write!(buf, "  Address: {:X} {} ", memory.address(self.cursor), region.perms)?;
+                // the line is sliced by bytes and & marks hotkeys
+                for ch in region.name.chars() {
+                    buf.push(if ch != '&' && ch.is_ascii() && !ch.is_ascii_control() { ch } else { '?' });
+                }
+            }
+        }
         // 2 & marks
         while buf.len() < self.win_size.columns as usize + 2 {
             buf.push(' ');
@@ -747,39 +1209,59 @@ https://github.com/panzi/rust-hox
         let _ = put_label(window, &buf[..min(self.win_size.columns as usize, buf.len())]);
 
         if self.offset_input.has_focus() {
-            self.offset_input.redraw(window, (rows - 6, 10))?;
+            self.offset_input.redraw(window, (rows - 15, 10))?;
         }
 
-        window.move_to((self.win_size.rows - 4, 0))?;
+        window.move_to((rows - 14, 0))?;
+        buf.clear();
+        if self.buffers.len() > 1 {
+            write!(buf, " File {}/{}: {}", self.current + 1, self.buffers.len(), self.path.display())?;
+        } else {
+            write!(buf, " File: {}", self.path.display())?;
+        }
+        let mut count = 0;
+        for ch in buf.chars().take(self.win_size.columns as usize) {
+            window.put_char(if ch.is_control() { '?' } else { ch })?;
+            count += 1;
+        }
+        for _ in count..self.win_size.columns as usize {
+            window.put_char(' ')?;
+        }
+
+        window.move_to((self.win_size.rows - 13, 0))?;
+
+        let mut value_buf = [0u8; 16];
+        let count = self.buffer.read(self.cursor, &mut value_buf);
+        let mem = &value_buf[..count];
 
         buf.clear();
         if self.signed {
-            if let Some(num) = get_i8(mem, self.cursor) {
+            if let Some(num) = get_i8(mem, 0) {
                 write!(buf, " int  8: {:>6}  ", num)?;
             } else {
                 buf.push_str(" int  8:         ");
             }
 
-            if let Some(num) = get_i32(mem, self.cursor, self.endian) {
+            if let Some(num) = get_i32(mem, 0, self.endian) {
                 write!(buf, "int 32: {:>20}  ", num)?;
             } else {
                 buf.push_str("int 32:                       ");
             }
         } else {
-            if let Some(num) = get_u8(mem, self.cursor) {
+            if let Some(num) = get_u8(mem, 0) {
                 write!(buf, " int  8: {:>6}  ", num)?;
             } else {
                 buf.push_str(" int  8:         ");
             }
 
-            if let Some(num) = get_u32(mem, self.cursor, self.endian) {
+            if let Some(num) = get_u32(mem, 0, self.endian) {
                 write!(buf, "int 32: {:>20}  ", num)?;
             } else {
                 buf.push_str("int 32:                       ");
             }
         }
 
-        if let Some(num) = get_f32(mem, self.cursor, self.endian) {
+        if let Some(num) = get_f32(mem, 0, self.endian) {
             write!(buf, "float 32: {:>20.6e}  ", num)?;
         } else {
             buf.push_str("float 32:                              ");
@@ -787,36 +1269,36 @@ https://github.com/panzi/rust-hox
 
         window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
 
-        window.move_to((self.win_size.rows - 3, 0))?;
+        window.move_to((self.win_size.rows - 12, 0))?;
 
         buf.clear();
         if self.signed {
-            if let Some(num) = get_i16(mem, self.cursor, self.endian) {
+            if let Some(num) = get_i16(mem, 0, self.endian) {
                 write!(buf, " int 16: {:>6}  ", num)?;
             } else {
                 buf.push_str(" int 16:         ");
             }
 
-            if let Some(num) = get_i64(mem, self.cursor, self.endian) {
+            if let Some(num) = get_i64(mem, 0, self.endian) {
                 write!(buf, "int 64: {:>20}  ", num)?;
             } else {
                 buf.push_str("int 64:                       ");
             }
         } else {
-            if let Some(num) = get_u16(mem, self.cursor, self.endian) {
+            if let Some(num) = get_u16(mem, 0, self.endian) {
                 write!(buf, " int 16: {:>6}  ", num)?;
             } else {
                 buf.push_str(" int 16:         ");
             }
 
-            if let Some(num) = get_u64(mem, self.cursor, self.endian) {
+            if let Some(num) = get_u64(mem, 0, self.endian) {
                 write!(buf, "int 64: {:>20}  ", num)?;
             } else {
                 buf.push_str("int 64:                       ");
             }
         }
 
-        if let Some(num) = get_f64(mem, self.cursor, self.endian) {
+        if let Some(num) = get_f64(mem, 0, self.endian) {
             write!(buf, "float 64: {:>20.6e}  ", num)?;
         } else {
             buf.push_str("float 64:                              ");
@@ -824,14 +1306,139 @@ https://github.com/panzi/rust-hox
 
         window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
 
-        if self.win_size.columns >= 5 {
-            window.move_to((self.win_size.rows - 1, self.win_size.columns - 5))?;
-            let pos = if size > 1 {
-                100 * self.cursor / (size - 1)
+        window.move_to((self.win_size.rows - 11, 0))?;
+
+        buf.clear();
+        if self.signed {
+            if let Some(num) = get_i24(mem, 0, self.endian) {
+                write!(buf, " int 24: {:>8}  ", num)?;
             } else {
-                100
-            };
-            window.put_str(format!("{:>3}%", pos))?;
+                buf.push_str(" int 24:           ");
+            }
+        } else {
+            if let Some(num) = get_u24(mem, 0, self.endian) {
+                write!(buf, " int 24: {:>8}  ", num)?;
+            } else {
+                buf.push_str(" int 24:           ");
+            }
+        }
+
+        if let Some(num) = get_f16(mem, 0, self.endian) {
+            write!(buf, "float 16: {:>13.6e}  ", num)?;
+        } else {
+            buf.push_str("float 16:                ");
+        }
+
+        if let Some(num) = get_bf16(mem, 0, self.endian) {
+            write!(buf, "bfloat 16: {:>13.6e}  ", num)?;
+        } else {
+            buf.push_str("bfloat 16:                ");
+        }
+
+        window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+
+        window.move_to((self.win_size.rows - 10, 0))?;
+
+        buf.clear();
+        if self.signed {
+            if let Some(num) = get_i128(mem, 0, self.endian) {
+                write!(buf, " int128: {:>40}  ", num)?;
+            } else {
+                buf.push_str(" int128:                                           ");
+            }
+        } else {
+            if let Some(num) = get_u128(mem, 0, self.endian) {
+                write!(buf, " int128: {:>40}  ", num)?;
+            } else {
+                buf.push_str(" int128:                                           ");
+            }
+        }
+
+        let uleb128 = get_uleb128(mem, 0);
+        if let Some((_, len)) = uleb128 {
+            write!(buf, "varint: {:>2} byte{}", len, if len == 1 { " " } else { "s" })?;
+        } else {
+            buf.push_str("varint:         ");
+        }
+
+        window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+
+        window.move_to((self.win_size.rows - 9, 0))?;
+
+        buf.clear();
+        if let Some((num, _)) = uleb128 {
+            write!(buf, " uleb128: {:>20}  ", num)?;
+        } else {
+            buf.push_str(" uleb128:                       ");
+        }
+
+        if let Some((num, _)) = get_sleb128(mem, 0) {
+            write!(buf, "sleb128: {:>20}  ", num)?;
+        } else {
+            buf.push_str("sleb128:                       ");
+        }
+
+        if let Some((num, _)) = uleb128 {
+            write!(buf, "zigzag: {:>20}  ", zigzag_decode(num))?;
+        } else {
+            buf.push_str("zigzag:                       ");
+        }
+
+        window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+
+        let endian = self.endian;
+        let timestamps = [
+            ("unix 32:",   get_i32(mem, 0, endian).map(|secs| timestamp::unix_secs(secs as i64))),
+            ("unix 64:",   get_i64(mem, 0, endian).map(timestamp::unix_secs)),
+            ("unix ms:",   get_i64(mem, 0, endian).map(timestamp::unix_millis)),
+            ("unix us:",   get_i64(mem, 0, endian).map(timestamp::unix_micros)),
+            ("FILETIME:",  get_u64(mem, 0, endian).map(timestamp::filetime)),
+            // DOS times have no time zone, all the others are UTC
+            ("DOS local:", get_u32(mem, 0, endian).map(timestamp::dos)),
+            ("HFS+:",      get_u32(mem, 0, endian).map(timestamp::hfs)),
+            ("NTP:",       get_u64(mem, 0, endian).map(timestamp::ntp)),
+        ];
+
+        for (row, pair) in timestamps.chunks(2).enumerate() {
+            window.move_to((self.win_size.rows - 8 + row as i32, 0))?;
+
+            buf.clear();
+            for (label, time) in pair {
+                // blank if there aren't enough bytes
+                let time = match time {
+                    Some(Some(time)) => time.as_str(),
+                    Some(None)       => "invalid",
+                    None             => "",
+                };
+                write!(buf, " {:<10} {:<27} ", label, time)?;
+            }
+
+            window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+        }
+
+        let [guid, ipv4, ipv6, mac] = structured_values(mem, self.endian);
+
+        window.move_to((self.win_size.rows - 4, 0))?;
+
+        buf.clear();
+        write!(buf, " {} {:<38}  {} {:<15}  {} {:<17}  ",
+            guid.0, guid.1.unwrap_or_default(),
+            ipv4.0, ipv4.1.unwrap_or_default(),
+            mac.0,  mac.1.unwrap_or_default())?;
+
+        window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+
+        window.move_to((self.win_size.rows - 3, 0))?;
+
+        buf.clear();
+        write!(buf, " {} {:<39}  ", ipv6.0, ipv6.1.unwrap_or_default())?;
+
+        window.put_str(&buf[..min(self.win_size.columns as usize, buf.len())])?;
+
+        if let Some(edit) = &self.inspector_edit {
+            let (row, column, _) = inspector_field_position(edit.field());
+            // TODO: correct truncating of NumberInput
+            let _ = edit.redraw(window, (self.win_size.rows - row, column));
         }
 
         window.move_to((self.win_size.rows - 1, 1))?;
@@ -849,10 +1456,46 @@ https://github.com/panzi/rust-hox
 
         buf.push_str("  [ &Help ]  [ &Quit ]");
 
+        buf.push_str(
+            if !self.buffer.is_modified() { "          " }
+            else                        { "  Modified" }
+        );
+
+        if let Some(job) = &self.search_job {
+            write!(buf, "  Searching {:>3}% 0x{:X}", job.percent(), self.buffer.backing().address(job.offset()))?;
+        } else {
+            buf.push_str(
+                if self.loading     { "  Reading  " }
+                else if self.follow { "  Following" }
+                else                { "           " }
+            );
+        }
+
+        if let Some((count, more)) = self.match_count {
+            buf.push_str("  ");
+            buf.push_str(&match_count_label(count, more));
+        }
+
         // ignore over long line errors here
         let _ = put_label(window, buf);
+        let _ = window.clear_to_end_of_line();
 
-        window.move_to((self.win_size.rows - 7, 0))?;
+        // the search progress takes the place of the cursor position
+        if self.win_size.columns >= 5 && self.search_job.is_none() {
+            window.move_to((self.win_size.rows - 1, self.win_size.columns - 5))?;
+            let pos = if size > 1 {
+                min(100 * self.cursor / (size - 1), 100)
+            } else {
+                100
+            };
+            window.put_str(format!("{:>3}%", pos))?;
+        }
+
+        if let Some(error) = self.buffer.take_error() {
+            self.error = Some(format!("{}: {:?}", error, self.path));
+        }
+
+        window.move_to((self.win_size.rows - 16, 0))?;
         if let Some(error) = &self.error {
             let mut error = error.replace('\n', " ");
             error.insert_str(0, "Error: ");
@@ -873,12 +1516,39 @@ https://github.com/panzi/rust-hox
         } else if self.search_widget.has_focus() {
             window.put_str(SEARCH_LABEL)?;
             self.search_widget.redraw(window, (self.win_size.rows - BOTTOM_WIN_HEIGHT as i32, SEARCH_LABEL.len() as i32))?;
+        } else if self.replace_widget.has_focus() {
+            window.put_str(REPLACE_LABEL)?;
+            self.replace_widget.redraw(window, (self.win_size.rows - BOTTOM_WIN_HEIGHT as i32, REPLACE_LABEL.len() as i32))?;
+        } else if let Some(edit) = &self.inspector_edit {
+            let prompt = format!("Edit {} at cursor: Enter writes, Tab/Up/Down other value, Escape cancels", edit.field().label());
+            let count = prompt.chars().count();
+            let _ = window.put_str(prompt);
+            for _ in count..self.win_size.columns as usize {
+                window.put_char(' ')?;
+            }
+        } else if let Some(replacing) = &self.replacing {
+            let prompt = replacing.prompt();
+            let count = prompt.chars().count();
+            let _ = window.put_str(prompt);
+            for _ in count..self.win_size.columns as usize {
+                window.put_char(' ')?;
+            }
+        } else if let Some(info) = &self.info {
+            let count = info.chars().count();
+            let _ = window.put_str(info);
+            for _ in count..self.win_size.columns as usize {
+                window.put_char(' ')?;
+            }
         } else {
             for _ in 0..self.win_size.columns {
                 window.put_char(' ')?;
             }
         }
 
+        if let Some((_, list_box)) = &self.list_box {
+            list_box.redraw(window)?;
+        }
+
         if self.help_shown {
             self.help_box.redraw(window)?;
         }
@@ -902,47 +1572,63 @@ https://github.com/panzi/rust-hox
             rows: win_size.rows,
         })?;
 
+        let label_len = REPLACE_LABEL.len() as i32;
+        self.replace_widget.resize(&Dimension {
+            columns: if win_size.columns > label_len { win_size.columns - label_len } else { 0 },
+            rows: win_size.rows,
+        })?;
+
         if self.help_shown {
             self.help_box.resize(&win_size)?;
         }
 
+        if let Some((_, list_box)) = &mut self.list_box {
+            list_box.resize(&win_size)?;
+        }
+
         if win_size.rows != self.win_size.rows || win_size.columns != self.win_size.columns {
             window.clear()?;
 
             self.win_size = win_size;
             self.need_redraw = true;
 
-            if self.win_size.rows < BOTTOM_WIN_HEIGHT as i32 || self.const_space + 3 > self.win_size.columns as usize {
-                self.bytes_per_row = 0;
-                self.view_size = 0;
-            } else {
-                let rest = self.win_size.columns as usize - self.const_space;
-                self.bytes_per_row = (rest + 1) / 4;
+            self.update_layout();
+        }
 
-                let view_rows = (self.win_size.rows - BOTTOM_WIN_HEIGHT as i32) as usize;
-                self.view_size = self.bytes_per_row * view_rows;
-            }
+        Ok(())
+    }
 
-            self.adjust_view();
+    fn update_layout(&mut self) {
+        if self.win_size.rows < BOTTOM_WIN_HEIGHT as i32 || self.const_space + 3 > self.win_size.columns as usize {
+            self.bytes_per_row = 0;
+            self.view_size = 0;
+        } else {
+            let rest = self.win_size.columns as usize - self.const_space;
+            self.bytes_per_row = (rest + 1) / 4;
+
+            let view_rows = (self.win_size.rows - BOTTOM_WIN_HEIGHT as i32) as usize;
+            self.view_size = self.bytes_per_row * view_rows;
         }
 
-        Ok(())
+        self.adjust_view();
     }
 
     fn adjust_view(&mut self) {
         if self.bytes_per_row > 0 {
-            let size = self.mmap.size();
-            let max_view_offset = if self.view_size < size {
-                size - size % self.bytes_per_row - (self.view_size - self.bytes_per_row)
+            let bytes_per_row = self.bytes_per_row as u64;
+            let view_size = self.view_size as u64;
+            let size = self.max_cursor() + 1;
+            let max_view_offset = if view_size < size {
+                size - size % bytes_per_row - (view_size - bytes_per_row)
             } else {
                 0
             };
 
-            if self.cursor >= self.view_offset + self.view_size {
-                self.view_offset = min(max_view_offset, self.cursor - self.cursor % self.bytes_per_row + self.bytes_per_row - self.view_size);
+            if self.cursor >= self.view_offset + view_size {
+                self.view_offset = min(max_view_offset, self.cursor - self.cursor % bytes_per_row + bytes_per_row - view_size);
                 self.need_redraw = true;
             } else if self.cursor < self.view_offset {
-                self.view_offset = min(max_view_offset, self.cursor - self.cursor % self.bytes_per_row);
+                self.view_offset = min(max_view_offset, self.cursor - self.cursor % bytes_per_row);
                 self.need_redraw = true;
             } else if self.view_offset > max_view_offset {
                 self.view_offset = max_view_offset;
@@ -954,15 +1640,16 @@ https://github.com/panzi/rust-hox
     fn handle(&mut self, input: Input) -> Result<bool> {
         match input {
             Input::KeyDown => {
-                let cursor = self.cursor + self.bytes_per_row;
-                if cursor < self.mmap.size() {
+                let cursor = self.cursor + self.bytes_per_row as u64;
+                if cursor <= self.max_cursor() {
                     self.set_cursor(cursor);
                 }
                 self.error = None;
             }
             Input::KeyUp => {
-                if self.cursor >= self.bytes_per_row {
-                    self.set_cursor(self.cursor - self.bytes_per_row);
+                let bytes_per_row = self.bytes_per_row as u64;
+                if self.cursor >= bytes_per_row {
+                    self.set_cursor(self.cursor - bytes_per_row);
                 }
                 self.error = None;
             }
@@ -978,15 +1665,16 @@ https://github.com/panzi/rust-hox
             }
             Input::KeyHome => {
                 if self.bytes_per_row > 0 {
-                    let cursor = self.cursor - self.cursor % self.bytes_per_row;
+                    let cursor = self.cursor - self.cursor % self.bytes_per_row as u64;
                     self.set_cursor(cursor);
                 }
                 self.error = None;
             }
             Input::KeyEnd => {
-                let size = self.mmap.size();
-                if size > 0 && self.bytes_per_row > 0 {
-                    let cursor = min(self.cursor + self.bytes_per_row - self.cursor % self.bytes_per_row , size) - 1;
+                let size = self.max_cursor() + 1;
+                if self.bytes_per_row > 0 {
+                    let bytes_per_row = self.bytes_per_row as u64;
+                    let cursor = min(self.cursor + bytes_per_row - self.cursor % bytes_per_row , size) - 1;
                     self.set_cursor(cursor);
                 }
                 self.error = None;
@@ -1034,17 +1722,15 @@ https://github.com/panzi/rust-hox
                 self.error = None;
             }
             Input::Character(DEVICE_CONTROL3) | Input::Character('$') => { // Ctrl+End
-                let size = self.mmap.size();
-                if size > 0 {
-                    self.set_cursor(size - 1);
-                }
+                self.set_cursor(self.max_cursor());
                 self.error = None;
             }
             Input::KeyPPage => {
+                let view_size = self.view_size as u64;
                 if self.view_offset > 0 {
-                    if self.view_offset >= self.view_size {
-                        let cursor = self.cursor - self.view_size;
-                        self.view_offset -= self.view_size;
+                    if self.view_offset >= view_size {
+                        let cursor = self.cursor - view_size;
+                        self.view_offset -= view_size;
                         self.set_cursor(cursor);
                     } else {
                         let cursor = self.cursor - self.view_offset;
@@ -1056,22 +1742,24 @@ https://github.com/panzi/rust-hox
                 self.error = None;
             }
             Input::KeyNPage => {
-                let size = self.mmap.size();
-                let max_view_offset = if self.view_size < size {
-                    size - size % self.bytes_per_row - (self.view_size - self.bytes_per_row)
+                let bytes_per_row = self.bytes_per_row as u64;
+                let view_size = self.view_size as u64;
+                let size = self.max_cursor() + 1;
+                let max_view_offset = if view_size < size {
+                    size - size % bytes_per_row - (view_size - bytes_per_row)
                 } else {
                     0
                 };
                 if self.view_offset < max_view_offset {
-                    let view_offset = self.view_offset + self.view_size;
-                    let cursor = self.cursor + self.view_size;
+                    let view_offset = self.view_offset + view_size;
+                    let cursor = self.cursor + view_size;
                     if view_offset > max_view_offset {
                         self.view_offset = max_view_offset;
                     } else {
                         self.view_offset = view_offset;
                     }
                     if cursor >= size {
-                        let cursor = cursor - self.bytes_per_row;
+                        let cursor = cursor - bytes_per_row;
                         self.set_cursor(cursor);
                     } else {
                         self.set_cursor(cursor);
@@ -1101,9 +1789,10 @@ https://github.com/panzi/rust-hox
                 if self.selecting {
                     self.selecting = false;
                 } else {
+                    self.selecting       = true;
+                    self.cursor          = min(self.cursor, self.max_cursor());
                     self.selection_start = self.cursor;
                     self.selection_end   = self.cursor + 1;
-                    self.selecting       = true;
                     self.view_mask_valid = false;
                 }
                 self.need_redraw = true;
@@ -1133,11 +1822,11 @@ https://github.com/panzi/rust-hox
             }
             Input::Character('#') => {
                 // select ASCII line under cursor
-                let mem = self.mmap.mem();
-                let size = mem.len();
+                let buffer = &self.buffer;
+                let is_ascii = |offset| buffer.get(offset).map(is_printable_ascii).unwrap_or(false);
                 self.error = None;
-                if size > 0 {
-                    if !is_printable_ascii(mem[self.cursor]) {
+                if !buffer.is_empty() {
+                    if !is_ascii(self.cursor) {
                         self.selecting = false;
                         self.error = Some("No ASCII character under cursor".to_owned());
                         let _ = self.curses.beep();
@@ -1146,14 +1835,14 @@ https://github.com/panzi/rust-hox
                     let mut start_index = self.cursor;
                     while start_index > 0 {
                         let index = start_index - 1;
-                        if !is_printable_ascii(mem[index]) {
+                        if !is_ascii(index) {
                             break;
                         }
                         start_index = index;
                     }
 
                     let mut end_index = self.cursor + 1;
-                    while end_index < size && is_printable_ascii(mem[end_index]) {
+                    while is_ascii(end_index) {
                         end_index += 1;
                     }
 
@@ -1166,10 +1855,42 @@ https://github.com/panzi/rust-hox
                     self.adjust_view();
                 }
             }
+            Input::Character('v') => {
+                // select varint under cursor
+                let mut value_buf = [0u8; MAX_VARINT_LEN];
+                let count = self.buffer.read(self.cursor, &mut value_buf);
+                if let Some((_, len)) = get_uleb128(&value_buf[..count], 0) {
+                    self.error = None;
+                    self.selection_start = self.cursor;
+                    self.selection_end   = self.cursor + len as u64;
+                    self.selecting       = false;
+                    self.need_redraw     = true;
+                    self.view_mask_valid = false;
+                } else {
+                    self.error = Some("No varint under cursor".to_owned());
+                    self.need_redraw = true;
+                    let _ = self.curses.beep();
+                }
+            }
+            Input::Character('c') => {
+                self.copy_structured_value();
+            }
+            Input::Character('I') => {
+                // edit a value of the inspector
+                self.error = None;
+                self.file_input.blur()?;
+                self.offset_input.blur()?;
+                self.rel_offset_input.blur()?;
+                self.search_widget.blur()?;
+                self.replace_widget.blur()?;
+                self.edit_inspector_field(self.inspector_field)?;
+            }
             Input::Character('o') => {
                 // goto offset
+                self.inspector_edit = None;
                 self.file_input.blur()?;
                 self.search_widget.blur()?;
+                self.replace_widget.blur()?;
                 self.rel_offset_input.blur()?;
                 self.offset_input.set_value(self.cursor)?;
                 self.offset_input.focus()?;
@@ -1178,9 +1899,11 @@ https://github.com/panzi/rust-hox
             }
             Input::Character('+') => {
                 // goto relative offset
+                self.inspector_edit = None;
                 self.file_input.blur()?;
                 self.offset_input.blur()?;
                 self.search_widget.blur()?;
+                self.replace_widget.blur()?;
                 self.rel_offset_input.set_plus()?;
                 self.rel_offset_input.focus()?;
                 self.need_redraw = true;
@@ -1188,9 +1911,11 @@ https://github.com/panzi/rust-hox
             }
             Input::Character('-') => {
                 // goto relative offset
+                self.inspector_edit = None;
                 self.file_input.blur()?;
                 self.offset_input.blur()?;
                 self.search_widget.blur()?;
+                self.replace_widget.blur()?;
                 self.rel_offset_input.set_minus()?;
                 self.rel_offset_input.focus()?;
                 self.need_redraw = true;
@@ -1198,17 +1923,26 @@ https://github.com/panzi/rust-hox
             }
             Input::Character('f') | Input::Character('/') | Input::KeyF3 => {
                 // search
+                self.inspector_edit = None;
                 self.error = None;
                 self.selecting = false;
                 self.file_input.blur()?;
                 self.offset_input.blur()?;
                 self.rel_offset_input.blur()?;
+                self.replace_widget.blur()?;
+                self.replacing = None;
                 if self.selection_end > self.selection_start {
-                    let search_data = &self.mmap.mem()[self.selection_start..self.selection_end];
-                    if search_data.iter().all(|byte| is_printable_ascii(*byte)) {
-                        self.search_widget.set_mode_and_value(SearchMode::String, search_data)?;
+                    let search_data = self.buffer.read_range(self.selection_start, self.selection_end);
+                    let mode = self.search_widget.mode();
+                    let is_text = mode.is_string() && mode.stringify(&search_data)
+                        .map(|text| text.chars().all(|ch| !ch.is_control()))
+                        .unwrap_or(false);
+                    if mode.is_regex() || is_text {
+                        self.search_widget.set_value(&search_data)?;
+                    } else if search_data.iter().all(|byte| is_printable_ascii(*byte)) {
+                        self.search_widget.set_mode_and_value(SearchMode::TEXT, &search_data)?;
                     } else {
-                        self.search_widget.set_mode_and_value(SearchMode::Binary, search_data)?;
+                        self.search_widget.set_mode_and_value(SearchMode::Binary, &search_data)?;
                     }
                 } else {
                     self.search_widget.set_value(&[])?;
@@ -1220,10 +1954,35 @@ https://github.com/panzi/rust-hox
                 // clear search
                 self.error = None;
                 self.search_widget.blur()?;
-                self.search_data.clear();
+                self.replace_widget.blur()?;
+                self.replacing = None;
+                self.search_pattern = None;
+                self.match_count = None;
                 self.view_mask_valid = false;
                 self.need_redraw = true;
             }
+            Input::Character('a') => {
+                self.error = None;
+                self.find_all();
+            }
+            Input::Character('R') => {
+                // replace
+                self.selecting = false;
+                self.need_redraw = true;
+                if self.search_pattern.is_some() {
+                    self.error = None;
+                    self.inspector_edit = None;
+                    self.file_input.blur()?;
+                    self.offset_input.blur()?;
+                    self.rel_offset_input.blur()?;
+                    self.search_widget.blur()?;
+                    self.replacing = None;
+                    self.replace_widget.focus()?;
+                } else {
+                    self.error = Some("Nothing to replace, search for something first".to_owned());
+                    let _ = self.curses.beep();
+                }
+            }
             Input::Character('n') | Input::Character('P') => {
                 self.find_next();
             }
@@ -1235,7 +1994,9 @@ https://github.com/panzi/rust-hox
                 if self.selection_start < self.selection_end {
                     self.error = None;
                     self.selecting = false;
+                    self.inspector_edit = None;
                     self.search_widget.blur()?;
+                    self.replace_widget.blur()?;
                     self.offset_input.blur()?;
                     self.rel_offset_input.blur()?;
                     self.file_input.set_value("")?;
@@ -1246,6 +2007,89 @@ https://github.com/panzi/rust-hox
                 }
                 self.need_redraw = true;
             }
+            Input::Character('E') | Input::KeyIC => {
+                // enter edit mode
+                self.error = None;
+                self.editing = true;
+                self.edit_low_nibble = false;
+                self.need_redraw = true;
+            }
+            Input::Character('u') => {
+                self.error = None;
+                self.undo();
+            }
+            Input::Character('U') | Input::Character(DEVICE_CONTROL2) => {
+                self.error = None;
+                self.redo();
+            }
+            Input::Character('W') | Input::KeyF2 => {
+                // save changes
+                self.error = None;
+                self.save();
+            }
+            Input::Character('r') => {
+                // pick memory region
+                self.error = None;
+                self.need_redraw = true;
+                if let Some(memory) = self.buffer.backing().process() {
+                    let mut text = String::new();
+                    for region in memory.regions() {
+                        if !text.is_empty() {
+                            text.push('\n');
+                        }
+                        write!(text, "{0:01$X} - {2:01$X}  {3}  {4}",
+                            region.start, self.offset_hex_len, region.end, region.perms, region.name)?;
+                    }
+                    let selected = memory.region_index(self.cursor).unwrap_or(0);
+                    let mut list_box = TextBox::list(text, 1, 0, selected);
+                    list_box.resize(&self.win_size)?;
+                    self.selecting = false;
+                    self.list_box  = Some((ListKind::Regions, list_box));
+                    self.need_redraw = true;
+                } else {
+                    self.error = Some("Memory regions are only available with --pid".to_owned());
+                    let _ = self.curses.beep();
+                }
+            }
+            Input::Character('t') => {
+                // toggle follow mode
+                if self.buffer.backing().file().is_ok() {
+                    self.follow = !self.follow;
+                    self.need_redraw = true;
+                } else {
+                    self.error = Some("Only files can be followed".to_owned());
+                    self.need_redraw = true;
+                    let _ = self.curses.beep();
+                }
+            }
+            Input::Character(']') if self.buffers.len() > 1 => {
+                // next file
+                self.switch_buffer((self.current + 1) % self.buffers.len());
+            }
+            Input::Character('[') if self.buffers.len() > 1 => {
+                // previous file
+                self.switch_buffer((self.current + self.buffers.len() - 1) % self.buffers.len());
+            }
+            Input::Character('b') => {
+                // pick open file
+                let mut text = String::new();
+                for index in 0..self.buffers.len() {
+                    let (path, modified) = if let Some(state) = &self.buffers[index] {
+                        (&state.path, state.buffer.is_modified())
+                    } else {
+                        (&self.path, self.buffer.is_modified())
+                    };
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    write!(text, "{:>2}  {}  {}", index + 1, if modified { '*' } else { ' ' }, path.display())?;
+                }
+                let mut list_box = TextBox::list(text, 1, 0, self.current);
+                list_box.resize(&self.win_size)?;
+                self.selecting = false;
+                self.list_box  = Some((ListKind::Buffers, list_box));
+                self.need_redraw = true;
+            }
             Input::Character('h') | Input::KeyF1 => {
                 // show help
                 self.selecting = false;
@@ -1255,7 +2099,20 @@ https://github.com/panzi/rust-hox
             }
             Input::Character('q') | Input::Character(END_OF_TRANSMISSION) => {
                 // quit program
-                return Ok(false)
+                let modified = self.buffers.iter().flatten()
+                    .find(|state| state.buffer.is_modified())
+                    .map(|state| state.path.clone());
+                if !self.buffer.is_modified() && modified.is_none() {
+                    return Ok(false)
+                }
+                self.quit_requested = true;
+                self.error = Some(if let Some(path) = modified.filter(|_| !self.buffer.is_modified()) {
+                    format!("Unsaved changes in {:?}, press q again to quit anyway", path)
+                } else {
+                    "Unsaved changes, press q again to quit anyway".to_owned()
+                });
+                self.need_redraw = true;
+                let _ = self.curses.beep();
             }
             _input => {}
         }
@@ -1267,13 +2124,41 @@ https://github.com/panzi/rust-hox
         self.resize()?;
 
         loop {
+            self.poll();
+
             if self.need_redraw {
                 self.redraw()?;
                 self.need_redraw = false;
             }
 
-            if let Some(input) = self.curses.window_mut().read_char() {
-                if self.help_shown {
+            let poll = self.loading || self.follow || self.buffer.backing().is_faulted() || self.search_job.is_some();
+            let window = self.curses.window_mut();
+            window.set_timeout(if poll { Some(POLL_INTERVAL) } else { None });
+
+            if let Some(input) = window.read_char() {
+                if self.info.take().is_some() {
+                    self.need_redraw = true;
+                }
+
+                let quit_requested = std::mem::replace(&mut self.quit_requested, false);
+                if quit_requested {
+                    if let Input::Character('q') | Input::Character(END_OF_TRANSMISSION) = input {
+                        break;
+                    }
+                }
+
+                if self.search_job.is_some() {
+                    // nothing may change while searching
+                    match input {
+                        Input::Character(ESCAPE) | Input::Character(END_OF_TEXT) => {
+                            self.cancel_search();
+                        }
+                        Input::KeyResize => {
+                            self.handle(input)?;
+                        }
+                        _ => {}
+                    }
+                } else if self.help_shown {
                     match input {
                         Input::Character('h') | Input::KeyF1 => {
                             self.help_shown  = false;
@@ -1288,7 +2173,7 @@ https://github.com/panzi/rust-hox
                                 TextBoxResult::Redraw => {
                                     self.need_redraw = true;
                                 }
-                                TextBoxResult::Ignore => {}
+                                TextBoxResult::Ignore | TextBoxResult::Selected(_) => {}
                                 TextBoxResult::Quit => {
                                     self.help_shown  = false;
                                     self.need_redraw = true;
@@ -1305,6 +2190,47 @@ https://github.com/panzi/rust-hox
                             }
                         }
                     }
+                } else if let Some((list_kind, list_box)) = &mut self.list_box {
+                    let list_kind = *list_kind;
+                    match list_box.handle(input)? {
+                        TextBoxResult::Redraw => {
+                            self.need_redraw = true;
+                        }
+                        TextBoxResult::Ignore => {}
+                        TextBoxResult::Quit => {
+                            self.list_box    = None;
+                            self.need_redraw = true;
+                            self.matches.clear();
+                            self.clear_bottom_bar();
+                        }
+                        TextBoxResult::Selected(index) => {
+                            self.list_box    = None;
+                            self.need_redraw = true;
+                            self.clear_bottom_bar();
+                            match list_kind {
+                                ListKind::Regions => {
+                                    if let Some(memory) = self.buffer.backing().process() {
+                                        let offset = memory.region_offset(index);
+                                        self.set_cursor(offset);
+                                    }
+                                }
+                                ListKind::Buffers => {
+                                    self.switch_buffer(index);
+                                }
+                                ListKind::Matches => {
+                                    if let Some(&(offset, _)) = self.matches.get(index) {
+                                        self.set_cursor(offset);
+                                    }
+                                    self.matches.clear();
+                                }
+                            }
+                        }
+                        TextBoxResult::PropagateEvent => {
+                            if !self.handle(input)? {
+                                break;
+                            }
+                        }
+                    }
                 } else if self.error.is_some() {
                     match input {
                         Input::Character(ch) if ch != 'h' => {
@@ -1329,13 +2255,16 @@ https://github.com/panzi/rust-hox
                         }
                         WidgetResult::Value(path) => {
                             self.need_redraw = true;
+                            self.add_file_history(&path);
                             match File::create(&path) {
-                                Ok(mut file) => {
+                                Ok(file) => {
                                     use std::io::Write;
 
-                                    let data = &self.mmap.mem()[self.selection_start..self.selection_end];
+                                    let mut writer = std::io::BufWriter::new(file);
+                                    let result = self.buffer.write_range(self.selection_start, self.selection_end, &mut writer)
+                                        .and_then(|_| writer.flush());
 
-                                    if let Err(error) = file.write_all(data) {
+                                    if let Err(error) = result {
                                         self.error = Some(format!("{}: {:?}", error, path));
                                         let _ = self.curses.beep();
                                     }
@@ -1361,13 +2290,44 @@ https://github.com/panzi/rust-hox
                         WidgetResult::Redraw => {
                             self.need_redraw = true;
                         }
-                        WidgetResult::Value(bytes) => {
-                            self.search_data = bytes;
+                        WidgetResult::Value(pattern) => {
+                            self.add_search_history();
+                            self.search_pattern = Some(pattern);
+                            self.replacing = None;
+                            self.match_count = None;
                             self.view_mask_valid = false;
                             self.need_redraw = true;
                             self.find_next();
                         }
                         WidgetResult::Beep => {
+                            if let Some(error) = self.search_widget.take_error() {
+                                self.error = Some(error);
+                                self.need_redraw = true;
+                            }
+                            let _ = self.curses.beep();
+                        }
+                        WidgetResult::Ignore => {}
+                    }
+                } else if self.replace_widget.has_focus() {
+                    match self.replace_widget.handle(input)? {
+                        WidgetResult::PropagateEvent => {
+                            if !self.handle(input)? {
+                                break;
+                            }
+                        }
+                        WidgetResult::Redraw => {
+                            self.need_redraw = true;
+                        }
+                        WidgetResult::Value(replacement) => {
+                            if let Pattern::Bytes(finder) = replacement {
+                                self.start_replace(finder.needle().to_vec());
+                            }
+                        }
+                        WidgetResult::Beep => {
+                            if let Some(error) = self.replace_widget.take_error() {
+                                self.error = Some(error);
+                                self.need_redraw = true;
+                            }
                             let _ = self.curses.beep();
                         }
                         WidgetResult::Ignore => {}
@@ -1404,15 +2364,13 @@ https://github.com/panzi/rust-hox
                         WidgetResult::Value(value) => {
                             let mut cursor = self.cursor;
                             if value < 0 {
-                                if -value as usize > cursor {
+                                if value.unsigned_abs() > cursor {
                                     cursor = 0;
                                 } else {
-                                    cursor -= -value as usize;
+                                    cursor -= value.unsigned_abs();
                                 }
-                            } else if value as usize > std::usize::MAX - cursor {
-                                cursor = cursor;
-                            } else {
-                                cursor += value as usize;
+                            } else if value as u64 <= std::u64::MAX - cursor {
+                                cursor += value as u64;
                             }
                             self.set_cursor(cursor);
                             self.need_redraw = true;
@@ -1422,6 +2380,31 @@ https://github.com/panzi/rust-hox
                         }
                         WidgetResult::Ignore => {}
                     }
+                } else if self.inspector_edit.is_some() {
+                    if !self.handle_inspector_edit(input)? {
+                        break;
+                    }
+                } else if self.replacing.is_some() {
+                    if !self.handle_replace(input)? {
+                        break;
+                    }
+                } else if self.editing {
+                    match self.handle_edit(input) {
+                        WidgetResult::PropagateEvent => {
+                            // only consecutive keystrokes form one undo step
+                            self.journal.close_step();
+                            if !self.handle(input)? {
+                                break;
+                            }
+                        }
+                        WidgetResult::Redraw => {
+                            self.need_redraw = true;
+                        }
+                        WidgetResult::Beep => {
+                            let _ = self.curses.beep();
+                        }
+                        WidgetResult::Value(()) | WidgetResult::Ignore => {}
+                    }
                 } else {
                     if !self.handle(input)? {
                         break;
@@ -1433,58 +2416,884 @@ https://github.com/panzi/rust-hox
         Ok(())
     }
 
-    fn find_next(&mut self) -> bool {
-        let search_data = &self.search_data[..];
-        let search_size = search_data.len();
-        if search_size > 0 {
-            let size = self.mmap.size();
+    // Opens another file as a buffer with the settings of the current one.
+    pub fn add_buffer(&mut self, buffer: Buffer, path: impl AsRef<Path>) {
+        let follow = self.follow && buffer.backing().file().is_ok();
+        self.buffers.push(Some(BufferState {
+            buffer,
+            path: path.as_ref().to_path_buf(),
+            journal: Journal::new(),
+            view_offset: 0,
+            cursor: 0,
+            selection_start: 0,
+            selection_end: 0,
+            selecting: false,
+            search_pattern: None,
+            match_count: None,
+            endian: self.endian,
+            signed: self.signed,
+            follow,
+        }));
+        self.need_redraw = true;
+    }
+
+    fn swap_buffer_state(&mut self, state: BufferState) -> BufferState {
+        BufferState {
+            buffer:          std::mem::replace(&mut self.buffer,          state.buffer),
+            path:            std::mem::replace(&mut self.path,            state.path),
+            journal:         std::mem::replace(&mut self.journal,         state.journal),
+            view_offset:     std::mem::replace(&mut self.view_offset,     state.view_offset),
+            cursor:          std::mem::replace(&mut self.cursor,          state.cursor),
+            selection_start: std::mem::replace(&mut self.selection_start, state.selection_start),
+            selection_end:   std::mem::replace(&mut self.selection_end,   state.selection_end),
+            selecting:       std::mem::replace(&mut self.selecting,       state.selecting),
+            search_pattern:  std::mem::replace(&mut self.search_pattern,  state.search_pattern),
+            match_count:     std::mem::replace(&mut self.match_count,     state.match_count),
+            endian:          std::mem::replace(&mut self.endian,          state.endian),
+            signed:          std::mem::replace(&mut self.signed,          state.signed),
+            follow:          std::mem::replace(&mut self.follow,          state.follow),
+        }
+    }
+
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current || index >= self.buffers.len() {
+            return;
+        }
+
+        self.journal.close_step();
+        let state = self.buffers[index].take().unwrap();
+        let state = self.swap_buffer_state(state);
+        self.buffers[self.current] = Some(state);
+        self.current = index;
+
+        self.edit_low_nibble = false;
+        self.quit_requested  = false;
+        self.replacing = None;
+        self.loading = self.buffer.is_growing();
+        self.buffer.refresh();
+
+        // the window might have been resized in the meantime
+        if self.bytes_per_row > 0 {
+            self.view_offset -= self.view_offset % self.bytes_per_row as u64;
+        }
+        let _ = self.curses.window_mut().clear();
+        self.buffer_changed();
+    }
+
+    // Picks up data that arrived on a stream in the meantime.
+    fn poll(&mut self) {
+        if let Some(job) = &self.search_job {
+            if job.is_interrupted() {
+                self.cancel_search();
+            } else if job.is_finished() {
+                let job = self.search_job.take().unwrap();
+                self.finish_search(job);
+            } else {
+                // progress display
+                self.need_redraw = true;
+            }
+        }
+
+        let loading = self.buffer.is_growing();
+        if self.buffer.refresh() {
+            self.buffer_changed();
+        }
+        if loading != self.loading {
+            self.loading = loading;
             self.need_redraw = true;
-            if search_size <= size {
-                let mem = self.mmap.mem();
-                let start_offset = self.cursor + 1;
-                let end_offset = size - search_size + 1;
-                for offset in start_offset..end_offset {
-                    if &mem[offset..offset + search_size] == search_data {
-                        self.error = None;
-                        self.set_cursor(offset);
-                        return true;
+        }
+        if self.follow || self.buffer.backing().is_faulted() {
+            self.follow_file();
+        }
+    }
+
+    // Picks up changes of the file on disk, like tail -f. Also remaps the file
+    // if it got truncated under the mapping.
+    fn follow_file(&mut self) {
+        let old_len = self.buffer.len();
+        let at_end = self.cursor + 1 >= old_len;
+        let faulted = self.buffer.backing().is_faulted();
+
+        match self.buffer.check_file(&self.path) {
+            Ok(FileChange::Unchanged) => {
+                if !faulted {
+                    return;
+                }
+            }
+            Ok(FileChange::Grew) => {
+                self.info = Some(format!("File grew by {} bytes to {} bytes", self.buffer.len() - old_len, self.buffer.len()));
+            }
+            Ok(FileChange::Shrank) => {
+                if self.buffer.is_truncated() {
+                    // The changes are kept, but the data they refer to reads
+                    // as zeros and saving is refused.
+                    self.follow = false;
+                    self.info = Some(format!("File shrank to {} bytes, stopped following to keep unsaved changes", self.buffer.backing().len()));
+                } else {
+                    if !self.buffer.is_modified() {
+                        // undo steps might refer to data that is gone
+                        self.journal = Journal::new();
                     }
+                    self.info = Some(format!("File shrank by {} bytes to {} bytes", old_len - self.buffer.len(), self.buffer.len()));
+                }
+            }
+            Ok(FileChange::Replaced) => {
+                let reloaded = if self.buffer.is_modified() {
+                    self.follow = false;
+                    self.info = Some("File was replaced, stopped following to keep unsaved changes".to_owned());
+                    false
+                } else {
+                    match File::open(&self.path).map_err(Error::from).and_then(Backing::open) {
+                        Ok(backing) => {
+                            self.buffer  = Buffer::new(backing);
+                            self.journal = Journal::new();
+                            self.info = Some(format!("File was replaced, reloaded {} bytes", self.buffer.len()));
+                            true
+                        }
+                        Err(error) => {
+                            self.follow = false;
+                            self.error  = Some(format!("{}: {:?}", error, self.path));
+                            false
+                        }
+                    }
+                };
+                if !reloaded {
+                    self.need_redraw = true;
+                    if !faulted {
+                        return;
+                    }
+                    // the file that is still open got truncated, too
+                    self.remap();
+                }
+            }
+            Err(Error { error_type: ErrorType::IO(error), .. }) if error.kind() == std::io::ErrorKind::NotFound => {
+                // might be in the middle of being replaced
+                if !faulted {
+                    return;
                 }
+                // but the file that is still open got truncated
+                self.remap();
             }
-            self.error = Some("Pattern not found searching forward".to_owned());
+            Err(error) => {
+                self.follow = false;
+                self.error  = Some(format!("{}: {:?}", error, self.path));
+                self.need_redraw = true;
+                if !faulted {
+                    return;
+                }
+                self.remap();
+            }
+        }
+
+        let size = self.buffer.len();
+        self.selection_start = min(self.selection_start, size);
+        self.selection_end   = min(self.selection_end,   size);
+        self.buffer_changed();
+        if at_end && !self.editing {
+            self.set_cursor(size);
+        }
+    }
+
+    // Handles a truncation of the open file that can't be picked up by
+    // following the path, so the fault is only reported once.
+    fn remap(&mut self) {
+        if let Err(error) = self.buffer.remap() {
+            self.follow = false;
+            self.error  = Some(format!("{}: {:?}", error, self.path));
+        }
+    }
+
+    fn handle_edit(&mut self, input: Input) -> WidgetResult<()> {
+        match input {
+            Input::Character(ESCAPE) => {
+                self.editing = false;
+                self.edit_low_nibble = false;
+                self.journal.close_step();
+                self.clamp_cursor();
+                WidgetResult::Redraw
+            }
+            Input::Character('\t') => {
+                self.edit_ascii = !self.edit_ascii;
+                self.edit_low_nibble = false;
+                self.journal.close_step();
+                WidgetResult::Redraw
+            }
+            Input::KeyIC => {
+                self.inserting = !self.inserting;
+                self.edit_low_nibble = false;
+                self.journal.close_step();
+                self.clamp_cursor();
+                self.adjust_view();
+                WidgetResult::Redraw
+            }
+            Input::KeyDC => {
+                if self.selection_end > self.selection_start {
+                    let start_offset = min(self.selection_start, self.buffer.len());
+                    let end_offset   = min(self.selection_end,   self.buffer.len());
+                    self.journal.close_step();
+                    self.selecting = false;
+                    self.replace(start_offset, end_offset - start_offset, &[]);
+                    self.journal.close_step();
+                    self.selection_start = 0;
+                    self.selection_end   = 0;
+                    self.set_location_cursor(start_offset);
+                } else if self.cursor < self.buffer.len() {
+                    self.replace(self.cursor, 1, &[]);
+                    self.set_location_cursor(self.cursor);
+                } else {
+                    return WidgetResult::Beep;
+                }
+                WidgetResult::Redraw
+            }
+            Input::KeyBackspace => {
+                if self.edit_low_nibble {
+                    self.journal.close_step();
+                    self.edit_low_nibble = false;
+                } else if self.inserting {
+                    if self.cursor == 0 {
+                        return WidgetResult::Beep;
+                    }
+                    let offset = self.cursor - 1;
+                    self.replace(offset, 1, &[]);
+                    self.set_location_cursor(offset);
+                } else if self.cursor > 0 {
+                    self.journal.close_step();
+                    self.set_cursor(self.cursor - 1);
+                }
+                WidgetResult::Redraw
+            }
+            Input::Character(ch) if self.edit_ascii => {
+                let cp = ch as u32;
+                if cp <= 0x1F || cp == 0x7F {
+                    return WidgetResult::PropagateEvent;
+                }
+                let mut buf = [0; 4];
+                let data = ch.encode_utf8(&mut buf).as_bytes();
+                let cursor = self.cursor;
+                if self.inserting {
+                    self.replace(cursor, 0, data);
+                } else if cursor < self.buffer.len() {
+                    self.overwrite(cursor, data);
+                } else {
+                    return WidgetResult::Beep;
+                }
+                self.set_location_cursor(cursor + data.len() as u64);
+                WidgetResult::Redraw
+            }
+            Input::Character(ch) if ch.is_ascii_hexdigit() => {
+                let nibble = ch.to_digit(16).unwrap() as u8;
+                let cursor = self.cursor;
+                if self.edit_low_nibble {
+                    let byte = self.buffer.get(cursor).unwrap_or(0);
+                    self.overwrite(cursor, &[(byte & 0xF0) | nibble]);
+                    self.set_location_cursor(cursor + 1);
+                } else if self.inserting {
+                    self.replace(cursor, 0, &[nibble << 4]);
+                    self.edit_low_nibble = true;
+                } else if let Some(byte) = self.buffer.get(cursor) {
+                    self.overwrite(cursor, &[(nibble << 4) | (byte & 0x0F)]);
+                    self.edit_low_nibble = true;
+                } else {
+                    return WidgetResult::Beep;
+                }
+                WidgetResult::Redraw
+            }
+            _input => WidgetResult::PropagateEvent
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            cursor:          self.cursor,
+            selection_start: self.selection_start,
+            selection_end:   self.selection_end,
+        }
+    }
+
+    fn set_location(&mut self, location: Location) {
+        let size = self.buffer.len();
+        let selecting = self.selecting;
+        self.selecting       = false;
+        self.set_cursor(location.cursor);
+        self.selecting       = selecting;
+        self.selection_start = min(location.selection_start, size);
+        self.selection_end   = min(location.selection_end,   size);
+        self.edit_low_nibble = false;
+        self.view_mask_valid = false;
+        self.need_redraw     = true;
+    }
+
+    // Move the cursor after an edit without touching the selection.
+    fn set_location_cursor(&mut self, cursor: u64) {
+        let location = Location { cursor, ..self.location() };
+        let edit_low_nibble = self.edit_low_nibble && cursor == self.cursor;
+        self.set_location(location);
+        self.edit_low_nibble = edit_low_nibble;
+    }
+
+    fn replace(&mut self, offset: u64, len: u64, data: &[u8]) {
+        let location = self.location();
+        let change = self.buffer.replace(offset, len, data);
+        self.journal.record(change, location);
+
+        // keep the selection on the same bytes
+        let data_len = data.len() as u64;
+        let map_offset = |pos: u64| {
+            if pos <= offset {
+                pos
+            } else if pos >= offset + len {
+                pos - len + data_len
+            } else {
+                offset + min(pos - offset, data_len)
+            }
+        };
+        self.selection_start = map_offset(self.selection_start);
+        self.selection_end   = map_offset(self.selection_end);
+
+        self.buffer_changed();
+    }
+
+    fn overwrite(&mut self, offset: u64, data: &[u8]) {
+        let size = self.buffer.len();
+        if offset < size {
+            let len = min(data.len() as u64, size - offset);
+            self.replace(offset, len, &data[..len as usize]);
+        }
+    }
+
+    fn buffer_changed(&mut self) {
+        let offset_hex_len = hex_len(self.buffer.backing().address(self.buffer.len()));
+        if offset_hex_len != self.offset_hex_len {
+            self.offset_hex_len = offset_hex_len;
+            self.const_space = offset_hex_len + 5;
+            let _ = self.curses.window_mut().clear();
+            self.update_layout();
+        }
+        if self.cursor > self.max_cursor() {
+            self.cursor = self.max_cursor();
+        }
+        self.match_count = None;
+        self.adjust_view();
+        self.need_redraw = true;
+    }
+
+    fn undo(&mut self) {
+        if let Some(step) = self.journal.undo() {
+            for change in step.changes.iter().rev() {
+                self.buffer.undo(change);
+            }
+            let location = step.location;
+            self.buffer_changed();
+            self.set_location(location);
+        } else {
+            self.error = Some("Already at oldest change".to_owned());
             let _ = self.curses.beep();
+            self.need_redraw = true;
         }
+    }
 
-        false
+    fn redo(&mut self) {
+        if let Some(step) = self.journal.redo() {
+            for change in &step.changes {
+                self.buffer.redo(change);
+            }
+            let mut location = step.location;
+            if let Some(change) = step.changes.last() {
+                location.cursor = change.offset;
+            }
+            self.buffer_changed();
+            self.set_location(location);
+        } else {
+            self.error = Some("Already at newest change".to_owned());
+            let _ = self.curses.beep();
+            self.need_redraw = true;
+        }
+    }
+
+    fn save(&mut self) {
+        match self.buffer.save(&self.path, &mut self.journal) {
+            Ok(()) => {
+                self.view_mask_valid = false;
+            }
+            Err(error) => {
+                self.error = Some(format!("{}: {:?}", error, self.path));
+                let _ = self.curses.beep();
+            }
+        }
+        self.need_redraw = true;
+    }
+
+    // Shows the GUID, IPv4, IPv6 and MAC address at the cursor in the status
+    // line so that it can be selected in the terminal, one after another on
+    // repeated presses.
+    fn copy_structured_value(&mut self) {
+        let mut value_buf = [0u8; 16];
+        let count = self.buffer.read(self.cursor, &mut value_buf);
+        let values = structured_values(&value_buf[..count], self.endian);
+
+        let previous = match self.copied {
+            Some((offset, index)) if offset == self.cursor => Some(index),
+            _ => None,
+        };
+        let found = next_structured_value(&values, previous);
+
+        self.need_redraw = true;
+        if let Some(index) = found {
+            let (label, value) = &values[index];
+            self.copied = Some((self.cursor, index));
+            self.info = Some(format!("{} {}", label, value.as_ref().unwrap()));
+        } else {
+            self.copied = None;
+            self.error = Some("Not enough bytes under cursor".to_owned());
+            let _ = self.curses.beep();
+        }
+    }
+
+    fn edit_inspector_field(&mut self, field: InspectorField) -> Result<()> {
+        let (_, _, width) = inspector_field_position(field);
+        self.inspector_field = field;
+        self.inspector_edit = Some(InspectorEdit::new(field, self.signed, width)?);
+        self.need_redraw = true;
+        Ok(())
     }
 
-    fn find_previous(&mut self) -> bool {
-        let search_data = &self.search_data[..];
-        let search_size = search_data.len();
-        if search_size > 0 {
-            let size = self.mmap.size();
+    fn handle_inspector_edit(&mut self, input: Input) -> Result<bool> {
+        let field = self.inspector_field;
+        match input {
+            Input::KeyDown | Input::Character('\t') => {
+                self.edit_inspector_field(field.next())?;
+                return Ok(true);
+            }
+            Input::KeyUp | Input::KeyBTab => {
+                self.edit_inspector_field(field.prev())?;
+                return Ok(true);
+            }
+            _ => {}
+        }
+
+        let Some(edit) = &mut self.inspector_edit else {
+            return Ok(true);
+        };
+
+        let result = edit.handle(input, self.endian)?;
+        if !edit.has_focus() {
+            self.inspector_edit = None;
             self.need_redraw = true;
-            if self.cursor > 0 {
-                let mem = self.mmap.mem();
-                let start_offset = min(self.cursor - 1, size - search_size);
-                let mut offset = start_offset;
-                loop {
-                    if &mem[offset..offset + search_size] == search_data {
-                        self.error = None;
-                        self.set_cursor(offset);
-                        return true;
+        }
+
+        match result {
+            WidgetResult::PropagateEvent => {
+                if !self.handle(input)? {
+                    return Ok(false);
+                }
+                // e.g. the signed toggle changes what values are allowed
+                let signed = self.signed;
+                if let Some(edit) = &mut self.inspector_edit {
+                    edit.set_signed(signed);
+                }
+            }
+            WidgetResult::Redraw => {
+                self.need_redraw = true;
+            }
+            WidgetResult::Value(data) => {
+                let cursor = self.cursor;
+                if cursor + data.len() as u64 > self.buffer.len() {
+                    self.error = Some("Not enough bytes under cursor".to_owned());
+                    let _ = self.curses.beep();
+                } else {
+                    self.journal.close_step();
+                    self.replace(cursor, data.len() as u64, &data);
+                    self.journal.close_step();
+                }
+                self.need_redraw = true;
+            }
+            WidgetResult::Beep => {
+                let _ = self.curses.beep();
+            }
+            WidgetResult::Ignore => {}
+        }
+
+        Ok(true)
+    }
+
+    fn add_search_history(&mut self) {
+        let text: Vec<char> = self.search_widget.text().chars().collect();
+        if let Err(error) = History::add_search(self.search_widget.mode(), &text) {
+            self.error = Some(format!("Error saving history: {}", error));
+        }
+    }
+
+    fn add_file_history(&mut self, path: &Path) {
+        let path: Vec<char> = path.to_string_lossy().chars().collect();
+        if let Err(error) = History::add_file(&path) {
+            self.error = Some(format!("Error saving history: {}", error));
+        }
+    }
+
+    fn find_next(&mut self) {
+        if self.search_pattern.is_some() {
+            self.start_search(self.cursor + 1, true);
+        }
+    }
+
+    fn find_previous(&mut self) {
+        if self.search_pattern.is_some() {
+            self.start_search(self.cursor, false);
+        }
+    }
+
+    fn find_all(&mut self) {
+        if self.search_job.is_some() || self.search_pattern.is_none() {
+            return;
+        }
+
+        if let Some((start_offset, end_offset)) = self.search_range() {
+            if let Some(pattern) = &self.search_pattern {
+                let job = SearchJob::find_all(&self.buffer, pattern, start_offset, end_offset, MAX_FIND_ALL);
+                self.run_search(job);
+            }
+        }
+    }
+
+    // Searching backward finds matches that start before start_offset.
+    fn start_search(&mut self, start_offset: u64, forward: bool) {
+        if self.search_job.is_some() || self.search_pattern.is_none() {
+            return;
+        }
+
+        if let Some(range) = self.search_range() {
+            if let Some(pattern) = &self.search_pattern {
+                let job = SearchJob::start(&self.buffer, pattern, start_offset, forward, range, self.search_widget.wraps());
+                self.run_search(job);
+            }
+        }
+    }
+
+    // The whole file or, if limited to it, the selection.
+    fn search_range(&mut self) -> Option<(u64, u64)> {
+        if !self.search_widget.in_selection() {
+            return Some((0, self.buffer.len()));
+        }
+
+        if self.selection_start >= self.selection_end {
+            self.error = Some("Nothing selected to search in".to_owned());
+            self.need_redraw = true;
+            let _ = self.curses.beep();
+            return None;
+        }
+
+        Some((self.selection_start, self.selection_end))
+    }
+
+    fn run_search(&mut self, job: Result<SearchJob>) {
+        self.need_redraw = true;
+        match job {
+            Ok(job) => {
+                if job.wait_for(SEARCH_WAIT) {
+                    self.finish_search(job);
+                } else {
+                    self.search_job = Some(job);
+                }
+            }
+            Err(error) => {
+                self.take_replace_search();
+                self.error = Some(error.to_string());
+                let _ = self.curses.beep();
+            }
+        }
+    }
+
+    fn finish_search(&mut self, job: SearchJob) {
+        let forward = job.is_forward();
+        self.need_redraw = true;
+        match job.join() {
+            Ok(SearchResult::Found(Some((offset, _)), wrapped)) => {
+                self.error = None;
+                if wrapped {
+                    self.info = Some(if forward {
+                        "Search wrapped around to the start"
+                    } else {
+                        "Search wrapped around to the end"
+                    }.to_owned());
+                }
+                self.set_cursor(offset);
+            }
+            Ok(SearchResult::Found(None, _)) => {
+                self.search_failed(forward);
+            }
+            Ok(SearchResult::All(matches, more)) => {
+                if let Some(in_selection) = self.take_replace_search() {
+                    self.confirm_replace_all(matches, in_selection);
+                } else if let Err(error) = self.show_matches(matches, more) {
+                    self.error = Some(error.to_string());
+                    let _ = self.curses.beep();
+                }
+            }
+            Err(error) => {
+                self.take_replace_search();
+                self.error = Some(error.to_string());
+                let _ = self.curses.beep();
+            }
+        }
+    }
+
+    fn show_matches(&mut self, matches: Vec<(u64, u64)>, more: bool) -> Result<()> {
+        self.match_count = Some((matches.len(), more));
+        if matches.is_empty() {
+            self.error = Some("Pattern not found".to_owned());
+            let _ = self.curses.beep();
+            return Ok(());
+        }
+
+        let mut text = String::new();
+        for &(offset, len) in &matches {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            write!(text, "{:01$X} ", self.buffer.backing().address(offset), self.offset_hex_len)?;
+
+            let preview_end = offset + min(len, MATCH_PREVIEW as u64);
+            for byte in self.buffer.read_range(offset, preview_end) {
+                write!(text, " {:02X}", byte)?;
+            }
+            let preview_len = (preview_end - offset) as usize;
+            text.push(if len > MATCH_PREVIEW as u64 { '…' } else { ' ' });
+            for _ in preview_len..MATCH_PREVIEW {
+                text.push_str("   ");
+            }
+
+            text.push_str("  ");
+            let context_start = offset.saturating_sub(MATCH_CONTEXT as u64);
+            let context_end = offset + min(len, MATCH_PREVIEW as u64) + MATCH_CONTEXT as u64;
+            for _ in (offset - context_start) as usize..MATCH_CONTEXT {
+                text.push(' ');
+            }
+            for byte in self.buffer.read_range(context_start, context_end) {
+                text.push(if is_sidebar_ascii(byte) { byte as char } else { '.' });
+            }
+        }
+
+        let selected = matches.partition_point(|&(offset, _)| offset < self.cursor);
+        let title = match_count_label(matches.len(), more);
+        let mut list_box = TextBox::list(text, 1, 0, min(selected, matches.len() - 1)).with_title(title);
+        list_box.resize(&self.win_size)?;
+        self.selecting = false;
+        self.matches = matches;
+        self.list_box = Some((ListKind::Matches, list_box));
+        self.need_redraw = true;
+
+        Ok(())
+    }
+
+    fn search_failed(&mut self, forward: bool) {
+        self.error = Some(if self.search_widget.in_selection() {
+            "Pattern not found in the selection"
+        } else if self.search_widget.wraps() {
+            "Pattern not found"
+        } else if forward {
+            "Pattern not found searching forward"
+        } else {
+            "Pattern not found searching backward"
+        }.to_owned());
+        self.need_redraw = true;
+        let _ = self.curses.beep();
+    }
+
+    // The cursor stays where it was.
+    fn cancel_search(&mut self) {
+        if let Some(job) = self.search_job.take() {
+            job.cancel();
+            self.take_replace_search();
+            self.info = Some("Search cancelled".to_owned());
+            self.need_redraw = true;
+        }
+    }
+
+    fn start_replace(&mut self, data: Vec<u8>) {
+        let label = format!("{:?} ({})", self.replace_widget.text(), self.replace_widget.mode());
+        self.replacing = Some(Replacing { data, label, state: ReplaceState::Prompt });
+        self.need_redraw = true;
+
+        if let Some(pattern) = &self.search_pattern {
+            if pattern.match_at(&self.buffer, self.cursor).is_none() {
+                self.find_next();
+            }
+        }
+    }
+
+    // Keys while replacing. All other keys work as usual, e.g. to move around.
+    fn handle_replace(&mut self, input: Input) -> Result<bool> {
+        let state = match &mut self.replacing {
+            Some(replacing) => std::mem::replace(&mut replacing.state, ReplaceState::Prompt),
+            None => return self.handle(input),
+        };
+
+        match state {
+            ReplaceState::Confirm { ranges, in_selection } => {
+                match input {
+                    Input::Character('y') => {
+                        self.replace_all(&ranges, in_selection);
                     }
-                    if offset == 0 {
-                        break;
+                    Input::KeyResize => {
+                        if let Some(replacing) = &mut self.replacing {
+                            replacing.state = ReplaceState::Confirm { ranges, in_selection };
+                        }
+                        return self.handle(input);
                     }
-                    offset -= 1;
+                    _ => {
+                        self.need_redraw = true;
+                    }
+                }
+            }
+            ReplaceState::Searching { in_selection } => {
+                if let Some(replacing) = &mut self.replacing {
+                    replacing.state = ReplaceState::Searching { in_selection };
                 }
+                return self.handle(input);
+            }
+            ReplaceState::Prompt => {
+                match input {
+                    Input::Character('y') => {
+                        self.replace_next();
+                    }
+                    Input::Character('n') => {
+                        self.find_next();
+                    }
+                    Input::Character('a') => {
+                        self.start_replace_all(false);
+                    }
+                    Input::Character('s') => {
+                        self.start_replace_all(true);
+                    }
+                    Input::Character('q') | Input::Character(ESCAPE) | Input::Character(END_OF_TRANSMISSION) => {
+                        self.replacing = None;
+                        self.need_redraw = true;
+                    }
+                    _ => {
+                        return self.handle(input);
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Replaces the match under the cursor and finds the next one.
+    fn replace_next(&mut self) {
+        let (Some(pattern), Some(replacing)) = (&self.search_pattern, &self.replacing) else {
+            return;
+        };
+
+        let Some(len) = pattern.match_at(&self.buffer, self.cursor) else {
+            self.find_next();
+            return;
+        };
+
+        let data = replacing.data.clone();
+        if !self.buffer.can_replace(&[(self.cursor, self.cursor + len)], data.len() as u64) {
+            self.error = Some("The size of this file can't change, the replacement has to be as long as the match".to_owned());
+            self.need_redraw = true;
+            let _ = self.curses.beep();
+            return;
+        }
+
+        let cursor = self.cursor;
+        self.journal.close_step();
+        self.replace(cursor, len, &data);
+        self.journal.close_step();
+        self.set_location_cursor(cursor + data.len() as u64);
+        self.start_search(self.cursor, true);
+    }
+
+    fn start_replace_all(&mut self, in_selection: bool) {
+        let (start_offset, end_offset) = if in_selection {
+            if self.selection_start >= self.selection_end {
+                self.error = Some("Nothing selected".to_owned());
+                self.need_redraw = true;
+                let _ = self.curses.beep();
+                return;
             }
-            self.error = Some("Pattern not found searching backward".to_owned());
+            (self.selection_start, self.selection_end)
+        } else {
+            (0, self.buffer.len())
+        };
+
+        if let (Some(pattern), Some(replacing)) = (&self.search_pattern, &mut self.replacing) {
+            replacing.state = ReplaceState::Searching { in_selection };
+            let job = SearchJob::find_all(&self.buffer, pattern, start_offset, end_offset, usize::MAX);
+            self.run_search(job);
+        }
+    }
+
+    // If the search was for replacing all returns whether it was only in the
+    // selection.
+    fn take_replace_search(&mut self) -> Option<bool> {
+        let replacing = self.replacing.as_mut()?;
+        if let ReplaceState::Searching { in_selection } = replacing.state {
+            replacing.state = ReplaceState::Prompt;
+            Some(in_selection)
+        } else {
+            None
+        }
+    }
+
+    fn confirm_replace_all(&mut self, matches: Vec<(u64, u64)>, in_selection: bool) {
+        let Some(replacing) = &mut self.replacing else {
+            return;
+        };
+
+        let ranges = replace_ranges(matches);
+        self.need_redraw = true;
+        if ranges.is_empty() {
+            self.error = Some(if in_selection {
+                "Pattern not found in the selection"
+            } else {
+                "Pattern not found"
+            }.to_owned());
+            let _ = self.curses.beep();
+        } else if !self.buffer.can_replace(&ranges, replacing.data.len() as u64) {
+            self.error = Some("The size of this file can't change, the replacement has to be as long as the matches".to_owned());
             let _ = self.curses.beep();
+        } else {
+            replacing.state = ReplaceState::Confirm { ranges, in_selection };
         }
+    }
 
-        false
+    fn replace_all(&mut self, ranges: &[(u64, u64)], in_selection: bool) {
+        let Some(replacing) = self.replacing.take() else {
+            return;
+        };
+        let data = replacing.data;
+        let data_len = data.len() as u64;
+
+        let location = self.location();
+        self.journal.close_step();
+        let change = self.buffer.replace_ranges(ranges, &data);
+        self.journal.record(change, location);
+        self.journal.close_step();
+
+        // keep the selection on the same bytes
+        let map_offset = |pos: u64| {
+            let index = ranges.partition_point(|&(_, end_offset)| end_offset <= pos);
+            let removed: u64 = ranges[..index].iter().map(|(start, end)| end - start).sum();
+            let mut new_pos = pos + index as u64 * data_len - removed;
+            if let Some(&(start, _)) = ranges.get(index) {
+                if start < pos {
+                    new_pos = new_pos - (pos - start) + min(pos - start, data_len);
+                }
+            }
+            new_pos
+        };
+        self.selection_start = map_offset(self.selection_start);
+        self.selection_end   = map_offset(self.selection_end);
+
+        self.buffer_changed();
+        self.set_location_cursor(ranges[0].0);
+        self.info = Some(format!("Replaced {} {}{}",
+            ranges.len(),
+            if ranges.len() == 1 { "match" } else { "matches" },
+            if in_selection { " in the selection" } else { "" }));
     }
 
     fn clear_bottom_bar(&mut self) {
@@ -1492,11 +3301,11 @@ https://github.com/panzi/rust-hox
         let win_size = window.size();
 
         if win_size.rows > BOTTOM_WIN_HEIGHT as i32 {
-            let size = self.mmap.size();
-            let bytes_per_row = self.bytes_per_row;
+            let size = self.buffer.len();
+            let bytes_per_row = self.bytes_per_row as u64;
 
             let row_count = if size > 0 && bytes_per_row > 0 {
-                let view_end_offset = min(self.view_offset + self.view_size, size);
+                let view_end_offset = min(self.view_offset + self.view_size as u64, size);
                 let actual_view_size = view_end_offset - self.view_offset;
                 if actual_view_size == 0 {
                     0
@@ -1518,13 +3327,13 @@ https://github.com/panzi/rust-hox
         }
     }
 
-    fn goto_percent(&mut self, percent: usize) {
-        let size = self.mmap.size();
+    fn goto_percent(&mut self, percent: u64) {
+        let size = self.buffer.len();
         if size > 1 {
             let max_offset = size - 1;
             if percent >= 100 {
                 self.set_cursor(max_offset);
-            } else if max_offset > std::usize::MAX / 100 {
+            } else if max_offset > std::u64::MAX / 100 {
                 // prevent integer overflow in multiplication
                 self.set_cursor((1 + ((max_offset - 1) / 100)) * percent);
             } else {
@@ -1533,3 +3342,158 @@ https://github.com/panzi/rust-hox
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_matches_are_replaced_once() {
+        assert_eq!(replace_ranges(vec![]), vec![]);
+        assert_eq!(replace_ranges(vec![(0, 2), (2, 2), (6, 1)]), vec![(0, 2), (2, 4), (6, 7)]);
+        // "aa" in "aaaa"
+        assert_eq!(replace_ranges(vec![(0, 2), (1, 2), (2, 2)]), vec![(0, 2), (2, 4)]);
+        assert_eq!(replace_ranges(vec![(0, 5), (1, 1), (3, 1), (5, 1)]), vec![(0, 5), (5, 6)]);
+    }
+
+    #[test]
+    fn int24() {
+        let mem = [0x01, 0x02, 0x03, 0xFF, 0xFF, 0xFF, 0x80, 0x00];
+        assert_eq!(get_u24(&mem, 0, Endian::Little), Some(0x030201));
+        assert_eq!(get_u24(&mem, 0, Endian::Big),    Some(0x010203));
+        assert_eq!(get_u24(&mem, 3, Endian::Little), Some(0xFFFFFF));
+        assert_eq!(get_i24(&mem, 3, Endian::Little), Some(-1));
+        assert_eq!(get_i24(&mem, 3, Endian::Big),    Some(-1));
+        assert_eq!(get_i24(&mem, 5, Endian::Big),    Some(-0x8000));
+        assert_eq!(get_i24(&mem, 5, Endian::Little), Some(0x0080FF));
+        assert_eq!(get_i24(&[0x00, 0x00, 0x80], 0, Endian::Little), Some(-0x80_0000));
+        assert_eq!(get_u24(&mem, 6, Endian::Little), None);
+        assert_eq!(get_i24(&mem, 9, Endian::Little), None);
+    }
+
+    #[test]
+    fn int128() {
+        let mut mem = [0u8; 17];
+        mem[1] = 1;
+        assert_eq!(get_u128(&mem, 1, Endian::Little), Some(1));
+        assert_eq!(get_u128(&mem, 1, Endian::Big),    Some(1 << 120));
+        assert_eq!(get_i128(&[0xFF; 16], 0, Endian::Big), Some(-1));
+        assert_eq!(get_u128(&[0xFF; 16], 0, Endian::Big), Some(u128::MAX));
+        assert_eq!(get_u128(&mem, 2, Endian::Little), None);
+    }
+
+    #[test]
+    fn float16() {
+        let f16 = |bits: u16| get_f16(&bits.to_be_bytes(), 0, Endian::Big).unwrap();
+        assert_eq!(f16(0x3C00), 1.0);
+        assert_eq!(f16(0xC000), -2.0);
+        assert_eq!(f16(0x7BFF), 65504.0);
+        assert_eq!(f16(0x7C00), f32::INFINITY);
+        assert_eq!(f16(0xFC00), f32::NEG_INFINITY);
+        assert!(f16(0x7E00).is_nan());
+        // smallest subnormal is 2^-24
+        assert_eq!(f16(0x0001), 1.0 / 16777216.0);
+        assert_eq!(f16(0x03FF), 1023.0 / 16777216.0);
+        assert_eq!(f16(0x8000).to_bits(), (-0.0f32).to_bits());
+
+        assert_eq!(get_f16(&[0x00, 0x3C], 0, Endian::Little), Some(1.0));
+        assert_eq!(get_f16(&[0x3C], 0, Endian::Little), None);
+    }
+
+    #[test]
+    fn uleb128() {
+        assert_eq!(get_uleb128(&[0x00], 0), Some((0, 1)));
+        assert_eq!(get_uleb128(&[0xE5, 0x8E, 0x26, 0xFF], 0), Some((624485, 3)));
+        assert_eq!(get_uleb128(&[0xFF, 0xE5, 0x8E, 0x26], 1), Some((624485, 3)));
+        // overlong encodings are still valid
+        assert_eq!(get_uleb128(&[0x80, 0x80, 0x00], 0), Some((0, 3)));
+        assert_eq!(get_uleb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01], 0), Some((u64::MAX, 10)));
+        // doesn't fit into 64 bits
+        assert_eq!(get_uleb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02], 0), None);
+        // unterminated at the end of the data
+        assert_eq!(get_uleb128(&[0x80, 0x80], 0), None);
+        assert_eq!(get_uleb128(&[], 0), None);
+        assert_eq!(get_uleb128(&[0x01], 2), None);
+        // longer than 10 bytes
+        assert_eq!(get_uleb128(&[0x80; 16], 0), None);
+        assert_eq!(get_uleb128(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 0), None);
+    }
+
+    #[test]
+    fn sleb128() {
+        assert_eq!(get_sleb128(&[0x7F], 0), Some((-1, 1)));
+        assert_eq!(get_sleb128(&[0x3F], 0), Some((63, 1)));
+        assert_eq!(get_sleb128(&[0x80, 0x7F], 0), Some((-128, 2)));
+        assert_eq!(get_sleb128(&[0xC0, 0xBB, 0x78], 0), Some((-123456, 3)));
+        assert_eq!(get_sleb128(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F], 0), Some((i64::MIN, 10)));
+        assert_eq!(get_sleb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00], 0), Some((i64::MAX, 10)));
+        assert_eq!(get_sleb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01], 0), None);
+        assert_eq!(get_sleb128(&[0xFF, 0xFF], 0), None);
+        assert_eq!(get_sleb128(&[0xFF; 16], 0), None);
+    }
+
+    #[test]
+    fn zigzag() {
+        assert_eq!(zigzag_decode(0), 0);
+        assert_eq!(zigzag_decode(1), -1);
+        assert_eq!(zigzag_decode(2), 1);
+        assert_eq!(zigzag_decode(3), -2);
+        assert_eq!(zigzag_decode(u64::MAX - 1), i64::MAX);
+        assert_eq!(zigzag_decode(u64::MAX), i64::MIN);
+    }
+
+    #[test]
+    fn guid() {
+        let mem = [
+            0xE0, 0x04, 0x25, 0x3F, 0x89, 0x4F, 0xD3, 0x11,
+            0x9A, 0x0C, 0x03, 0x05, 0xE8, 0x2C, 0x33, 0x01,
+        ];
+        assert_eq!(format_guid(&mem, 0, Endian::Little).unwrap(), "{3F2504E0-4F89-11D3-9A0C-0305E82C3301}");
+        assert_eq!(format_guid(&mem, 0, Endian::Big).unwrap(),    "{E004253F-894F-D311-9A0C-0305E82C3301}");
+        assert_eq!(format_guid(&mem, 1, Endian::Little), None);
+    }
+
+    #[test]
+    fn ip_and_mac() {
+        assert_eq!(format_ipv4(&[192, 168, 0, 1], 0).unwrap(), "192.168.0.1");
+        assert_eq!(format_ipv4(&[192, 168, 0], 0), None);
+        assert_eq!(format_mac(&[0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E], 0).unwrap(), "00:1A:2B:3C:4D:5E");
+        assert_eq!(format_mac(&[0x00, 0x1A, 0x2B, 0x3C, 0x4D], 0), None);
+
+        // the address is read from the cursor on, with 0xFF around it
+        let mut mem = [0u8; 18];
+        mem[0] = 0xFF;
+        mem[1..5].copy_from_slice(&[0x20, 0x01, 0x0D, 0xB8]);
+        mem[16] = 0x01;
+        mem[17] = 0xFF;
+        assert_eq!(format_ipv6(&mem, 1).unwrap(), "2001:db8::1");
+        assert_eq!(format_ipv6(&mem, 2).unwrap(), "10d:b800::1ff");
+        assert_eq!(format_ipv6(&mem, 3), None);
+        assert_eq!(format_ipv6(&mem, 18), None);
+        assert_eq!(format_ipv6(&[0; 15], 0), None);
+    }
+
+    #[test]
+    fn structured_value_cycle() {
+        let values = structured_values(&[0x7F, 0, 0, 1, 0xAA, 0xBB, 0xCC, 0xDD], Endian::Little);
+        assert_eq!(values[1].1.as_deref(), Some("127.0.0.1"));
+        assert_eq!(values[3].1.as_deref(), Some("7F:00:00:01:AA:BB"));
+
+        // GUID and IPv6 need 16 bytes, so only IPv4 and MAC are cycled through
+        assert_eq!(next_structured_value(&values, None),    Some(1));
+        assert_eq!(next_structured_value(&values, Some(1)), Some(3));
+        assert_eq!(next_structured_value(&values, Some(3)), Some(1));
+
+        let values = structured_values(&[0; 3], Endian::Little);
+        assert_eq!(next_structured_value(&values, None), None);
+    }
+
+    #[test]
+    fn bfloat16() {
+        assert_eq!(get_bf16(&[0x3F, 0x80], 0, Endian::Big), Some(1.0));
+        assert_eq!(get_bf16(&[0x80, 0x3F], 0, Endian::Little), Some(1.0));
+        assert_eq!(get_bf16(&[0xC0, 0x00], 0, Endian::Big), Some(-2.0));
+        assert_eq!(get_bf16(&[0x7F, 0x80], 0, Endian::Big), Some(f32::INFINITY));
+        assert_eq!(get_bf16(&[0x3F, 0x80], 1, Endian::Big), None);
+    }
+}
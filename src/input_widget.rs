@@ -26,6 +26,18 @@ pub enum WidgetResult<V> {
     Value(V),
 }
 
+impl<V> WidgetResult<V> {
+    pub fn map<U>(self, f: impl FnOnce(V) -> U) -> WidgetResult<U> {
+        match self {
+            WidgetResult::PropagateEvent => WidgetResult::PropagateEvent,
+            WidgetResult::Redraw => WidgetResult::Redraw,
+            WidgetResult::Ignore => WidgetResult::Ignore,
+            WidgetResult::Beep   => WidgetResult::Beep,
+            WidgetResult::Value(value) => WidgetResult::Value(f(value)),
+        }
+    }
+}
+
 pub trait InputWidget<InValue, OutValue=InValue> {
     fn has_focus(&self) -> bool {
         false
@@ -0,0 +1,270 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use pancurses_result::{Window, Point, Input};
+
+use crate::input_widget::{InputWidget, WidgetResult};
+use crate::number_input::NumberInput;
+use crate::result::Result;
+use crate::hox::Endian;
+
+// The values of the inspector that can be edited.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InspectorField {
+    Int8,
+    Int16,
+    Int24,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+}
+
+impl InspectorField {
+    pub fn next(&self) -> Self {
+        match self {
+            InspectorField::Int8    => InspectorField::Int16,
+            InspectorField::Int16   => InspectorField::Int24,
+            InspectorField::Int24   => InspectorField::Int32,
+            InspectorField::Int32   => InspectorField::Int64,
+            InspectorField::Int64   => InspectorField::Float32,
+            InspectorField::Float32 => InspectorField::Float64,
+            InspectorField::Float64 => InspectorField::Int8,
+        }
+    }
+
+    pub fn prev(&self) -> Self {
+        match self {
+            InspectorField::Int8    => InspectorField::Float64,
+            InspectorField::Int16   => InspectorField::Int8,
+            InspectorField::Int24   => InspectorField::Int16,
+            InspectorField::Int32   => InspectorField::Int24,
+            InspectorField::Int64   => InspectorField::Int32,
+            InspectorField::Float32 => InspectorField::Int64,
+            InspectorField::Float64 => InspectorField::Float32,
+        }
+    }
+
+    // number of bytes the value takes
+    pub fn size(&self) -> usize {
+        match self {
+            InspectorField::Int8    => 1,
+            InspectorField::Int16   => 2,
+            InspectorField::Int24   => 3,
+            InspectorField::Int32   => 4,
+            InspectorField::Int64   => 8,
+            InspectorField::Float32 => 4,
+            InspectorField::Float64 => 8,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, InspectorField::Float32 | InspectorField::Float64)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            InspectorField::Int8    => "int 8",
+            InspectorField::Int16   => "int 16",
+            InspectorField::Int24   => "int 24",
+            InspectorField::Int32   => "int 32",
+            InspectorField::Int64   => "int 64",
+            InspectorField::Float32 => "float 32",
+            InspectorField::Float64 => "float 64",
+        }
+    }
+
+    // inclusive range of the integer values
+    fn int_limits(&self, signed: bool) -> (i128, i128) {
+        let bits = self.size() as u32 * 8;
+        if signed {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        }
+    }
+}
+
+// Integers are entered as i128 so that signed and unsigned values of all sizes
+// fit, the limits of the field are checked by the input.
+enum ValueInput {
+    Int(NumberInput<i128>),
+    Float(NumberInput<f64>),
+}
+
+// A value of the inspector being edited in place.
+pub struct InspectorEdit {
+    field:  InspectorField,
+    signed: bool,
+    input:  ValueInput,
+}
+
+impl InspectorEdit {
+    pub fn new(field: InspectorField, signed: bool, size: usize) -> Result<Self> {
+        let input = if field.is_float() {
+            let mut input = NumberInput::new(size);
+            if field == InspectorField::Float32 {
+                input.set_limits(-f32::MAX as f64, f32::MAX as f64);
+            } else {
+                // 1e999 parses as infinity
+                input.set_limits(-f64::MAX, f64::MAX);
+            }
+            input.focus()?;
+            ValueInput::Float(input)
+        } else {
+            let mut input = NumberInput::new(size);
+            let (min, max) = field.int_limits(signed);
+            input.set_limits(min, max);
+            input.focus()?;
+            ValueInput::Int(input)
+        };
+
+        Ok(Self { field, signed, input })
+    }
+
+    #[inline]
+    pub fn field(&self) -> InspectorField {
+        self.field
+    }
+
+    pub fn set_signed(&mut self, signed: bool) {
+        if self.signed != signed {
+            self.signed = signed;
+            if let ValueInput::Int(input) = &mut self.input {
+                let (min, max) = self.field.int_limits(signed);
+                input.set_limits(min, max);
+            }
+        }
+    }
+
+    // Returns the encoded bytes on Enter.
+    pub fn handle(&mut self, input: Input, endian: Endian) -> Result<WidgetResult<Vec<u8>>> {
+        let size = self.field.size();
+        Ok(match &mut self.input {
+            ValueInput::Int(widget) => widget.handle(input)?.map(|num| {
+                match endian {
+                    Endian::Little => num.to_le_bytes()[..size].to_vec(),
+                    Endian::Big    => num.to_be_bytes()[16 - size..].to_vec(),
+                }
+            }),
+            ValueInput::Float(widget) => widget.handle(input)?.map(|num| {
+                match (self.field, endian) {
+                    (InspectorField::Float32, Endian::Little) => (num as f32).to_le_bytes().to_vec(),
+                    (InspectorField::Float32, Endian::Big)    => (num as f32).to_be_bytes().to_vec(),
+                    (_, Endian::Little) => num.to_le_bytes().to_vec(),
+                    (_, Endian::Big)    => num.to_be_bytes().to_vec(),
+                }
+            }),
+        })
+    }
+
+    pub fn has_focus(&self) -> bool {
+        match &self.input {
+            ValueInput::Int(input)   => input.has_focus(),
+            ValueInput::Float(input) => input.has_focus(),
+        }
+    }
+
+    pub fn redraw<P>(&self, window: &mut Window, pos: P) -> Result<()>
+    where P: Into<Point>, P: Copy {
+        match &self.input {
+            ValueInput::Int(input)   => input.redraw(window, pos),
+            ValueInput::Float(input) => input.redraw(window, pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enter(edit: &mut InspectorEdit, text: &str, endian: Endian) -> WidgetResult<Vec<u8>> {
+        for ch in text.chars() {
+            edit.handle(Input::Character(ch), endian).unwrap();
+        }
+        edit.handle(Input::Character('\n'), endian).unwrap()
+    }
+
+    fn edit(field: InspectorField, signed: bool, text: &str, endian: Endian) -> WidgetResult<Vec<u8>> {
+        let mut edit = InspectorEdit::new(field, signed, 20).unwrap();
+        enter(&mut edit, text, endian)
+    }
+
+    #[test]
+    fn int_limits() {
+        use InspectorField::*;
+        assert_eq!(Int8.int_limits(false),  (0, 0xFF));
+        assert_eq!(Int8.int_limits(true),   (-0x80, 0x7F));
+        assert_eq!(Int24.int_limits(false), (0, 0xFF_FFFF));
+        assert_eq!(Int24.int_limits(true),  (-0x80_0000, 0x7F_FFFF));
+        assert_eq!(Int64.int_limits(false), (0, u64::MAX as i128));
+        assert_eq!(Int64.int_limits(true),  (i64::MIN as i128, i64::MAX as i128));
+    }
+
+    #[test]
+    fn int8_range() {
+        use InspectorField::Int8;
+        let le = Endian::Little;
+        assert_eq!(edit(Int8, false, "255",  le), WidgetResult::Value(vec![0xFF]));
+        assert_eq!(edit(Int8, false, "256",  le), WidgetResult::Beep);
+        assert_eq!(edit(Int8, false, "128",  le), WidgetResult::Value(vec![0x80]));
+        assert_eq!(edit(Int8, false, "-1",   le), WidgetResult::Beep);
+        assert_eq!(edit(Int8, false, "-128", le), WidgetResult::Beep);
+        assert_eq!(edit(Int8, true,  "-128", le), WidgetResult::Value(vec![0x80]));
+        assert_eq!(edit(Int8, true,  "-129", le), WidgetResult::Beep);
+        assert_eq!(edit(Int8, true,  "127",  le), WidgetResult::Value(vec![0x7F]));
+        assert_eq!(edit(Int8, true,  "128",  le), WidgetResult::Beep);
+    }
+
+    #[test]
+    fn set_signed_changes_limits() {
+        let mut edit = InspectorEdit::new(InspectorField::Int8, false, 20).unwrap();
+        edit.set_signed(true);
+        assert_eq!(enter(&mut edit, "200", Endian::Little), WidgetResult::Beep);
+        assert!(edit.has_focus());
+        edit.set_signed(false);
+        assert_eq!(enter(&mut edit, "", Endian::Little), WidgetResult::Value(vec![200]));
+        assert!(!edit.has_focus());
+    }
+
+    #[test]
+    fn int_encoding() {
+        use InspectorField::*;
+        let (le, be) = (Endian::Little, Endian::Big);
+        assert_eq!(edit(Int24, true,  "-2",       le), WidgetResult::Value(vec![0xFE, 0xFF, 0xFF]));
+        assert_eq!(edit(Int24, true,  "-2",       be), WidgetResult::Value(vec![0xFF, 0xFF, 0xFE]));
+        assert_eq!(edit(Int24, false, "1193046",  le), WidgetResult::Value(vec![0x56, 0x34, 0x12]));
+        assert_eq!(edit(Int24, false, "1193046",  be), WidgetResult::Value(vec![0x12, 0x34, 0x56]));
+        assert_eq!(edit(Int24, false, "16777216", le), WidgetResult::Beep);
+        assert_eq!(edit(Int16, false, "4660",     be), WidgetResult::Value(vec![0x12, 0x34]));
+        assert_eq!(edit(Int32, true,  "-1",       le), WidgetResult::Value(vec![0xFF; 4]));
+        assert_eq!(edit(Int64, false, "18446744073709551615", be), WidgetResult::Value(vec![0xFF; 8]));
+        assert_eq!(edit(Int64, false, "18446744073709551616", be), WidgetResult::Beep);
+    }
+
+    #[test]
+    fn float_limits_and_encoding() {
+        use InspectorField::*;
+        let (le, be) = (Endian::Little, Endian::Big);
+        assert_eq!(edit(Float32, false, "1.5",    le), WidgetResult::Value(1.5f32.to_le_bytes().to_vec()));
+        assert_eq!(edit(Float32, false, "1.5",    be), WidgetResult::Value(1.5f32.to_be_bytes().to_vec()));
+        assert_eq!(edit(Float32, false, "-3e38",  le), WidgetResult::Value((-3e38f32).to_le_bytes().to_vec()));
+        assert_eq!(edit(Float32, false, "1e39",   le), WidgetResult::Beep);
+        assert_eq!(edit(Float32, false, "-1e39",  le), WidgetResult::Beep);
+        assert_eq!(edit(Float64, false, "1e300",  be), WidgetResult::Value(1e300f64.to_be_bytes().to_vec()));
+        assert_eq!(edit(Float64, false, "1e999",  le), WidgetResult::Beep);
+        assert_eq!(edit(Float64, false, "-1e999", le), WidgetResult::Beep);
+    }
+}
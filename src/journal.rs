@@ -0,0 +1,211 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::buffer::{Piece, pieces_len};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Location {
+    pub cursor:          u64,
+    pub selection_start: u64,
+    pub selection_end:   u64,
+}
+
+// Changes only reference the (immutable) file mapping and the (append-only)
+// buffer of added bytes, so no data of the file is copied. The only exception
+// are bytes that get overwritten when saving in place, of which one copy is
+// shared by all changes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Change {
+    pub offset: u64,
+    pub old: Vec<Piece>,
+    pub new: Vec<Piece>,
+}
+
+impl Change {
+    #[inline]
+    pub fn old_len(&self) -> u64 {
+        pieces_len(&self.old)
+    }
+
+    #[inline]
+    pub fn new_len(&self) -> u64 {
+        pieces_len(&self.new)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Step {
+    pub changes: Vec<Change>,
+    pub location: Location,
+}
+
+pub struct Journal {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    step_open: bool,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            step_open: false,
+        }
+    }
+
+    pub fn record(&mut self, change: Change, location: Location) {
+        self.redo.clear();
+
+        if self.step_open {
+            if let Some(step) = self.undo.last_mut() {
+                step.changes.push(change);
+                return;
+            }
+        }
+
+        self.undo.push(Step {
+            changes: vec![change],
+            location,
+        });
+        self.step_open = true;
+    }
+
+    // The next recorded change will start a new undo step.
+    #[inline]
+    pub fn close_step(&mut self) {
+        self.step_open = false;
+    }
+
+    pub fn undo(&mut self) -> Option<&Step> {
+        self.step_open = false;
+        let step = self.undo.pop()?;
+        self.redo.push(step);
+        self.redo.last()
+    }
+
+    pub fn redo(&mut self) -> Option<&Step> {
+        self.step_open = false;
+        let step = self.redo.pop()?;
+        self.undo.push(step);
+        self.undo.last()
+    }
+
+    pub fn pieces_mut(&mut self) -> impl Iterator<Item=&mut Vec<Piece>> {
+        self.undo.iter_mut().chain(self.redo.iter_mut())
+            .flat_map(|step| step.changes.iter_mut())
+            .flat_map(|change| [&mut change.old, &mut change.new])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backing::Backing;
+    use crate::buffer::Buffer;
+
+    fn buffer(data: &[u8]) -> Buffer {
+        let mut buffer = Buffer::new(Backing::Empty(std::fs::File::open("/dev/null").unwrap()));
+        buffer.replace(0, 0, data);
+        buffer
+    }
+
+    fn contents(buffer: &Buffer) -> Vec<u8> {
+        buffer.read_range(0, buffer.len())
+    }
+
+    fn location(cursor: u64, selection_start: u64, selection_end: u64) -> Location {
+        Location { cursor, selection_start, selection_end }
+    }
+
+    fn undo(buffer: &mut Buffer, journal: &mut Journal) -> Option<Location> {
+        let step = journal.undo()?;
+        for change in step.changes.iter().rev() {
+            buffer.undo(change);
+        }
+        Some(step.location)
+    }
+
+    fn redo(buffer: &mut Buffer, journal: &mut Journal) -> Option<Location> {
+        let step = journal.redo()?;
+        for change in &step.changes {
+            buffer.redo(change);
+        }
+        Some(step.location)
+    }
+
+    #[test]
+    fn consecutive_changes_form_one_step() {
+        let mut buffer  = buffer(b"0123456789");
+        let mut journal = Journal::new();
+
+        journal.record(buffer.replace(2, 1, b"a"), location(2, 0, 0));
+        journal.record(buffer.replace(3, 1, b"b"), location(3, 0, 0));
+        journal.record(buffer.replace(4, 1, b"c"), location(4, 0, 0));
+        // the editor closes the step when the cursor is moved by other means
+        journal.close_step();
+        journal.record(buffer.replace(8, 1, b"d"), location(8, 0, 0));
+        assert_eq!(contents(&buffer), b"01abc567d9");
+
+        assert_eq!(undo(&mut buffer, &mut journal), Some(location(8, 0, 0)));
+        assert_eq!(contents(&buffer), b"01abc56789");
+        assert_eq!(undo(&mut buffer, &mut journal), Some(location(2, 0, 0)));
+        assert_eq!(contents(&buffer), b"0123456789");
+        assert_eq!(undo(&mut buffer, &mut journal), None);
+    }
+
+    #[test]
+    fn undo_redo_restores_bytes_and_location() {
+        let mut buffer  = buffer(b"hello world");
+        let mut journal = Journal::new();
+        let before = location(6, 6, 11);
+
+        journal.record(buffer.replace(6, 5, b"there"), before);
+        journal.close_step();
+        journal.record(buffer.replace(5, 1, b""), location(5, 0, 0));
+        journal.close_step();
+        assert_eq!(contents(&buffer), b"hellothere");
+
+        assert_eq!(undo(&mut buffer, &mut journal), Some(location(5, 0, 0)));
+        assert_eq!(undo(&mut buffer, &mut journal), Some(before));
+        assert_eq!(contents(&buffer), b"hello world");
+
+        assert_eq!(redo(&mut buffer, &mut journal), Some(before));
+        assert_eq!(contents(&buffer), b"hello there");
+
+        // a new change drops the steps that were undone
+        journal.record(buffer.replace(0, 1, b"j"), location(0, 0, 0));
+        assert_eq!(redo(&mut buffer, &mut journal), None);
+        assert_eq!(contents(&buffer), b"jello there");
+    }
+
+    #[test]
+    fn replace_all_is_one_step() {
+        let mut buffer  = buffer(b"a-b-c-d");
+        let mut journal = Journal::new();
+        let before = location(0, 0, 7);
+
+        journal.record(buffer.replace_ranges(&[(1, 2), (3, 4), (5, 6)], b"::"), before);
+        journal.close_step();
+        assert_eq!(contents(&buffer), b"a::b::c::d");
+
+        assert_eq!(undo(&mut buffer, &mut journal), Some(before));
+        assert_eq!(contents(&buffer), b"a-b-c-d");
+        assert_eq!(undo(&mut buffer, &mut journal), None);
+
+        assert_eq!(redo(&mut buffer, &mut journal), Some(before));
+        assert_eq!(contents(&buffer), b"a::b::c::d");
+    }
+}
@@ -13,20 +13,62 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
 
+// The code base predates these lints and follows its own style, e.g. explicit
+// returns, `match` instead of `matches!` and `std::u64::MAX`. They are only
+// allowed so that clippy can run on the whole crate without rewriting code
+// that has no other reason to change, new code should not rely on them.
+#![allow(
+    clippy::char_lit_as_u8,
+    clippy::collapsible_if,
+    clippy::collapsible_match,
+    clippy::explicit_counter_loop,
+    clippy::implicit_saturating_sub,
+    clippy::into_iter_on_ref,
+    clippy::legacy_numeric_constants,
+    clippy::len_zero,
+    clippy::manual_flatten,
+    clippy::manual_range_contains,
+    clippy::match_like_matches_macro,
+    clippy::needless_borrow,
+    clippy::needless_range_loop,
+    clippy::needless_return,
+    clippy::same_item_push,
+    clippy::unnecessary_cast,
+    clippy::useless_conversion,
+    clippy::while_let_loop,
+)]
+
 use clap::{Arg, Command};
 
 mod result;
 mod hox;
 mod mmap;
+mod mapped;
+mod block_cache;
+mod buffer;
+mod backing;
+mod spool;
+mod paged;
+mod process;
 mod input_widget;
 mod number_input;
 mod file_input;
 mod text_box;
 mod search_widget;
+mod pattern;
+mod substring;
+mod search_job;
+mod history;
+mod timestamp;
+mod inspector;
+mod journal;
 mod consts;
 
 use result::Result;
 use hox::{Hox, Endian, Theme};
+use buffer::Buffer;
+use backing::Backing;
+use process::ProcessMemory;
 
 fn main() {
     let args = Command::new("Hox - Hex viewer written in Rust")
@@ -57,13 +99,50 @@ fn main() {
             .takes_value(false)
             .help("Burn your eyes in light mode."))
 
+        .arg(Arg::new("follow")
+            .long("follow")
+            .short('f')
+            .takes_value(false)
+            .conflicts_with("pid")
+            .help("Watch the file for changes and keep the cursor at the end if it is there, like tail -f."))
+
+        .arg(Arg::new("pid")
+            .long("pid")
+            .short('p')
+            .takes_value(true)
+            .value_name("PID")
+            .conflicts_with("file")
+            .help("Show the memory of the process PID."))
+
         .arg(Arg::new("file")
             .index(1)
-            .required(true)
-            .value_name("FILE"))
+            .multiple_occurrences(true)
+            .required_unless_present("pid")
+            .value_name("FILE")
+            .help("Files to open. Use - to read from standard input."))
         .get_matches();
 
-    let filename = args.value_of("file").unwrap();
+    let pid = if let Some(pid) = args.value_of("pid") {
+        if let Ok(pid) = pid.parse::<u32>() {
+            Some(pid)
+        } else {
+            eprintln!("Error: illegal value for --pid: {:?}", pid);
+            std::process::exit(1);
+        }
+    } else {
+        None
+    };
+
+    let filenames: Vec<String> = if let Some(pid) = pid {
+        vec![format!("/proc/{}/mem", pid)]
+    } else {
+        args.values_of("file").unwrap().map(str::to_owned).collect()
+    };
+
+    if filenames.iter().filter(|filename| *filename == "-").count() > 1 {
+        eprintln!("Error: standard input can only be opened once");
+        std::process::exit(1);
+    }
 
     let endian = args.value_of("endian").unwrap();
     let endian = if endian.eq_ignore_ascii_case("little") {
@@ -76,27 +155,52 @@ fn main() {
     };
 
     let signed = args.is_present("signed");
+    let follow = args.is_present("follow");
     let theme = if args.is_present("light-mode") {
         Theme::Light
     } else {
         Theme::Dark
     };
 
-    if let Err(mut error) = run(filename, endian, signed, theme) {
-        if error.path().is_none() {
-            error = error.with_path(filename);
+    if let Err(mut error) = run(&filenames, pid, endian, signed, follow, theme) {
+        if error.path().is_none() && filenames.len() == 1 {
+            error = error.with_path(&filenames[0]);
         }
         eprintln!("Error: {}", error);
         std::process::exit(1);
     }
 }
 
-fn run(filename: &str, endian: Endian, signed: bool, theme: Theme) -> Result<()> {
-    let mut file = std::fs::File::open(filename)?;
+fn open(filename: &str, pid: Option<u32>) -> Result<Buffer> {
+    let backing = if let Some(pid) = pid {
+        Backing::Process(ProcessMemory::open(pid)?)
+    } else if filename == "-" {
+        Backing::open(spool::take_stdin()?)?
+    } else {
+        Backing::open(std::fs::File::open(filename)?)?
+    };
 
-    let mut hox = Hox::new(&mut file, theme)?;
+    Ok(Buffer::new(backing))
+}
+
+fn run(filenames: &[String], pid: Option<u32>, endian: Endian, signed: bool, follow: bool, theme: Theme) -> Result<()> {
+    let mut buffers = Vec::with_capacity(filenames.len());
+    for filename in filenames {
+        let buffer = open(filename, pid).map_err(|error|
+            if error.path().is_none() { error.with_path(filename) } else { error })?;
+        buffers.push(buffer);
+    }
+    let mut buffers = filenames.iter().zip(buffers);
+    let (filename, buffer) = buffers.next().unwrap();
+
+    let mut hox = Hox::new(buffer, filename, theme)?;
     hox.set_endian(endian);
     hox.set_signed(signed);
+    hox.set_follow(follow);
+
+    for (filename, buffer) in buffers {
+        hox.add_buffer(buffer, filename);
+    }
 
     hox.run()
 }
@@ -0,0 +1,130 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::cell::Cell;
+
+use crate::mmap::MMap;
+use crate::block_cache::{Block, BlockCache};
+
+// multiple of the page size of every platform we care about
+const CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_CHUNKS: usize = 8;
+
+impl Block for MMap {
+    #[inline]
+    fn size(&self) -> usize {
+        MMap::size(self)
+    }
+
+    #[inline]
+    fn read(&self, offset: usize, buf: &mut [u8]) -> std::io::Result<()> {
+        MMap::read(self, offset, buf)
+    }
+}
+
+// Maps a file in aligned chunks and keeps only the most recently used ones
+// mapped, i.e. the ones around the view, the search position and the
+// selection. That way the file size isn't limited by the address space.
+pub struct Mapped {
+    file:    File,
+    size:    u64,
+    chunks:  BlockCache<MMap>,
+    faulted: Cell<bool>,
+}
+
+impl Mapped {
+    pub fn new(file: File, size: u64) -> std::io::Result<Self> {
+        let mapped = Self {
+            file,
+            size,
+            chunks:  BlockCache::new(CHUNK_SIZE, MAX_CHUNKS),
+            faulted: Cell::new(false),
+        };
+
+        // fail early for files that can't be mapped at all
+        if size > 0 {
+            mapped.read(0, &mut [0])?;
+        }
+
+        Ok(mapped)
+    }
+
+    // An independent mapping of the same file.
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Mapped::new(self.file.try_clone()?, self.size)
+    }
+
+    #[inline]
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // Drops all chunks, which are mapped again on demand.
+    pub fn remap(&mut self, size: u64) {
+        self.chunks.clear();
+        self.size = size;
+        self.faulted.set(false);
+    }
+
+    // Stays set when the faulted chunk gets unmapped.
+    #[inline]
+    pub fn is_faulted(&self) -> bool {
+        self.faulted.get()
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        let result = self.chunks.read(self.size, offset, buf, |chunk_offset, chunk_size|
+            MMap::new(&self.file, chunk_offset, chunk_size));
+        // a faulted chunk fails the read, so it is still mapped here
+        if result.is_err() && self.chunks.any(|mmap| mmap.is_faulted()) {
+            self.faulted.set(true);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fault_outlives_the_chunk() {
+        let path = std::env::temp_dir().join(format!("hox-test-{}-mapped-fault", std::process::id()));
+        let size = CHUNK_SIZE * (MAX_CHUNKS as u64 + 1);
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        file.set_len(size).unwrap();
+        let mapped = Mapped::new(file.try_clone().unwrap(), size).unwrap();
+
+        // cut off the last chunk
+        let mut buf = [0; 1];
+        file.set_len(size - CHUNK_SIZE).unwrap();
+        assert!(mapped.read(size - 1, &mut buf).is_err());
+        assert!(mapped.is_faulted());
+
+        // unmaps the faulted chunk
+        for index in 0..MAX_CHUNKS as u64 {
+            mapped.read(index * CHUNK_SIZE, &mut buf).unwrap();
+        }
+        assert!(!mapped.chunks.any(|mmap| mmap.is_faulted()));
+        assert!(mapped.is_faulted());
+    }
+}
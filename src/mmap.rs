@@ -14,19 +14,174 @@
 // along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::os::unix::io::AsRawFd;
+use std::sync::Once;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
-pub struct MMap<'a> {
+const GUARDS_PER_BLOCK: usize = 64;
+
+// Address range of a mapping, so the SIGBUS handler can tell if a fault
+// happened in one of ours.
+//
+// The handler can't take locks, so every guard is a seqlock: seq is odd while
+// start and end are changed, and the handler skips guards whose seq was odd or
+// changed while it read them. That never skips the guard of the faulting
+// mapping, because a guard is only changed by the thread that registers or
+// drops its mapping, and a mapping isn't dropped while it is read from.
+struct Guard {
+    used:    AtomicBool,
+    seq:     AtomicUsize,
+    start:   AtomicUsize,
+    end:     AtomicUsize,
+    faulted: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const UNUSED_GUARD: Guard = Guard {
+    used:    AtomicBool::new(false),
+    seq:     AtomicUsize::new(0),
+    start:   AtomicUsize::new(0),
+    end:     AtomicUsize::new(0),
+    faulted: AtomicBool::new(false),
+};
+
+// Guards live in a linked list of blocks that is only ever appended to, so the
+// handler can walk it without locks. Blocks are never freed, unused guards are
+// reused instead.
+struct GuardBlock {
+    guards: [Guard; GUARDS_PER_BLOCK],
+    next:   AtomicPtr<GuardBlock>,
+}
+
+static GUARDS: GuardBlock = GuardBlock {
+    guards: [UNUSED_GUARD; GUARDS_PER_BLOCK],
+    next:   AtomicPtr::new(std::ptr::null_mut()),
+};
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+static INSTALL_HANDLER: Once = Once::new();
+
+impl Guard {
+    fn set_range(&self, start: usize, end: usize) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.start.store(start, Ordering::Relaxed);
+        self.end.store(end, Ordering::Relaxed);
+        self.seq.fetch_add(1, Ordering::Release);
+    }
+
+    // Only reads atomics, so it is safe to call from the signal handler.
+    fn contains(&self, addr: usize) -> bool {
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq & 1 != 0 {
+            return false;
+        }
+        let start = self.start.load(Ordering::Relaxed);
+        let end   = self.end.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) != seq {
+            return false;
+        }
+        addr >= start && addr < end
+    }
+}
+
+// Only reads atomics, so it is safe to call from the signal handler.
+fn guards() -> impl Iterator<Item=&'static Guard> {
+    std::iter::successors(Some(&GUARDS), |block| unsafe { block.next.load(Ordering::Acquire).as_ref() })
+        .flat_map(|block| block.guards.iter())
+}
+
+// Touching pages of a mapped file that was truncated raises SIGBUS. Instead of
+// dying, the missing page is replaced by a page of zeros and the mapping is
+// marked as faulted, so the read can report an error.
+//
+// POSIX doesn't list mmap() as async-signal-safe. On Linux it is a plain
+// system call in glibc and musl that takes no locks in user space, and the
+// kernel replaces the page atomically with MAP_FIXED. The fault happened in a
+// read of our own mapping, so nothing else is using that page meanwhile.
+extern "C" fn handle_sigbus(_signum: libc::c_int, info: *mut libc::siginfo_t, _context: *mut libc::c_void) {
+    unsafe {
+        let addr = (*info).si_addr() as usize;
+        if let Some(guard) = guards().find(|guard| guard.contains(addr)) {
+            let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+            let page = addr - addr % page_size;
+            let ptr = libc::mmap(page as *mut libc::c_void, page_size, libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED, -1, 0);
+            if ptr != libc::MAP_FAILED {
+                guard.faulted.store(true, Ordering::Release);
+                return;
+            }
+        }
+
+        // not ours, die like we would have without the handler
+        libc::signal(libc::SIGBUS, libc::SIG_DFL);
+    }
+}
+
+fn install_handler() {
+    INSTALL_HANDLER.call_once(|| unsafe {
+        PAGE_SIZE.store(libc::sysconf(libc::_SC_PAGESIZE) as usize, Ordering::Relaxed);
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigbus as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut());
+    });
+}
+
+fn register_guard(start: usize, end: usize) -> &'static Guard {
+    let mut block = &GUARDS;
+    loop {
+        for guard in &block.guards {
+            if guard.used.compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                guard.faulted.store(false, Ordering::Release);
+                guard.set_range(start, end);
+                return guard;
+            }
+        }
+
+        let mut next = block.next.load(Ordering::Acquire);
+        if next.is_null() {
+            let new_block = Box::into_raw(Box::new(GuardBlock {
+                guards: [UNUSED_GUARD; GUARDS_PER_BLOCK],
+                next:   AtomicPtr::new(std::ptr::null_mut()),
+            }));
+            next = match block.next.compare_exchange(std::ptr::null_mut(), new_block, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => new_block,
+                Err(other) => {
+                    // another thread appended a block first
+                    drop(unsafe { Box::from_raw(new_block) });
+                    other
+                }
+            };
+        }
+        block = unsafe { &*next };
+    }
+}
+
+fn unregister_guard(guard: &Guard) {
+    guard.set_range(0, 0);
+    guard.used.store(false, Ordering::Release);
+}
+
+// The mapping stays valid when the file is closed, so it doesn't borrow it.
+pub struct MMap {
     ptr: *mut libc::c_void,
     size: usize,
-    phantom: std::marker::PhantomData<&'a libc::c_void>,
+    guard: &'static Guard,
 }
 
-impl<'a> MMap<'a> {
-    pub fn new(file: &'a mut std::fs::File, offset: u64, size: usize) -> std::io::Result<Self> {
+// read-only and owned, so it can be moved to a search thread
+unsafe impl Send for MMap {}
+
+impl MMap {
+    pub fn new(file: &std::fs::File, offset: u64, size: usize) -> std::io::Result<Self> {
         if size > libc::size_t::MAX as usize || offset > libc::off_t::MAX as u64 {
             return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
         }
 
+        install_handler();
+
         let fd = file.as_raw_fd();
 
         unsafe {
@@ -36,14 +191,37 @@ impl<'a> MMap<'a> {
                 return Err(std::io::Error::last_os_error());
             }
 
+            let guard = register_guard(ptr as usize, ptr as usize + size);
+
             Ok(Self {
                 ptr,
                 size,
-                phantom: std::marker::PhantomData,
+                guard,
             })
         }
     }
 
+    // Set when the file was truncated while it was read. The missing parts
+    // read as zeros then.
+    #[inline]
+    pub fn is_faulted(&self) -> bool {
+        self.guard.faulted.load(Ordering::Acquire)
+    }
+
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> std::io::Result<()> {
+        let mem = self.mem();
+        if offset > mem.len() || buf.len() > mem.len() - offset {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&mem[offset..offset + buf.len()]);
+
+        if self.is_faulted() {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file was truncated while reading it"));
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub fn size(&self) -> usize {
         self.size
@@ -70,15 +248,16 @@ impl<'a> MMap<'a> {
     }
 }
 
-impl<'a> AsRef<[u8]> for MMap<'a> {
+impl AsRef<[u8]> for MMap {
     #[inline]
     fn as_ref(&self) -> &[u8] {
         self.mem()
     }
 }
 
-impl Drop for MMap<'_> {
+impl Drop for MMap {
     fn drop(&mut self) {
+        unregister_guard(self.guard);
         let result = unsafe {
             libc::munmap(self.ptr, self.size as libc::size_t) 
         };
@@ -87,3 +266,64 @@ impl Drop for MMap<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_count() -> usize {
+        std::iter::successors(Some(&GUARDS), |block| unsafe { block.next.load(Ordering::Acquire).as_ref() }).count()
+    }
+
+    #[test]
+    fn more_mappings_than_guards_per_block() {
+        let path = std::env::temp_dir().join(format!("hox-test-{}-many-mappings", std::process::id()));
+        std::fs::write(&path, [0xCD; 16]).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        let mut mmaps = Vec::new();
+        for _ in 0..GUARDS_PER_BLOCK * 3 + 1 {
+            mmaps.push(MMap::new(&file, 0, 16).unwrap());
+        }
+        // the guard of the last mapping is in a block that was added
+        let last = mmaps.last().unwrap();
+        assert_eq!(guards().filter(|guard| guard.contains(last.mem().as_ptr() as usize)).count(), 1);
+
+        // truncation is detected in every mapping
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(0).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let mut buf = [0; 16];
+        assert!(last.read(0, &mut buf).is_err());
+        assert_eq!(buf, [0; 16]);
+        assert!(mmaps[0].read(0, &mut buf).is_err());
+
+        // guards of dropped mappings get reused
+        let blocks = block_count();
+        mmaps.clear();
+        let file = std::fs::File::open("/proc/self/exe").unwrap();
+        for _ in 0..GUARDS_PER_BLOCK * 3 {
+            mmaps.push(MMap::new(&file, 0, 16).unwrap());
+        }
+        assert_eq!(block_count(), blocks);
+    }
+
+    #[test]
+    fn truncated_file_reads_as_error() {
+        let path = std::env::temp_dir().join(format!("hox-test-{}-truncated", std::process::id()));
+        let size = 4 * 64 * 1024;
+        std::fs::write(&path, vec![0xAB; size]).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let mmap = MMap::new(&file, 0, size).unwrap();
+
+        let mut buf = [0; 16];
+        mmap.read(size - 16, &mut buf).unwrap();
+        assert_eq!(buf, [0xAB; 16]);
+
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(0).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(mmap.read(size - 16, &mut buf).is_err());
+        assert!(mmap.is_faulted());
+        assert_eq!(buf, [0; 16]);
+    }
+}
@@ -26,19 +26,23 @@ use pancurses_result::{
     Input, Point, Window, ColorPair,
 };
 
+// longest input accepted, enough for 64 bit integers and floats with exponent
+const MAX_LEN: usize = 24;
+
 pub struct NumberInput<N>
-where N: FromStr, N: Display {
+where N: FromStr, N: Display, N: PartialOrd {
     focused: bool,
     size: usize,
     buf:  String,
     cursor: usize,
     view_offset: usize,
     error: bool,
-    phantom: std::marker::PhantomData<N>,
+    // inclusive range of accepted values
+    limits: Option<(N, N)>,
 }
 
 impl<N> NumberInput<N>
-where N: FromStr, N: Display {
+where N: FromStr, N: Display, N: PartialOrd {
     pub fn new(size: usize) -> Self {
         Self {
             focused: false,
@@ -47,8 +51,23 @@ where N: FromStr, N: Display {
             cursor: 0,
             view_offset: 0,
             error: false,
-            phantom: std::marker::PhantomData,
+            limits: None,
+        }
+    }
+
+    pub fn set_limits(&mut self, min: N, max: N) {
+        self.limits = Some((min, max));
+        self.error = !self.buf.is_empty() && self.parse().is_none();
+    }
+
+    fn parse(&self) -> Option<N> {
+        let num = self.buf.parse().ok()?;
+        if let Some((min, max)) = &self.limits {
+            if num < *min || num > *max {
+                return None;
+            }
         }
+        Some(num)
     }
 
     // since we control the characters that can be in buf we know its ASCII
@@ -126,7 +145,7 @@ where N: FromStr, N: Display {
 }
 
 impl<N> InputWidget<N> for NumberInput<N>
-where N: FromStr, N: Display {
+where N: FromStr, N: Display, N: PartialOrd {
     fn has_focus(&self) -> bool {
         self.focused
     }
@@ -219,9 +238,9 @@ where N: FromStr, N: Display {
 
         match input {
             Input::Character(ch) if ((ch >= '0' && ch <= '9') || ch == '+' || ch == '-' || ch == '.' || ch == 'e' || ch == 'E') => {
-                if self.buf.len() < 20 {
+                if self.buf.len() < MAX_LEN {
                     self.buf.insert(self.cursor, ch);
-                    self.error = self.buf.parse::<N>().is_err();
+                    self.error = self.parse().is_none();
                     self.cursor += 1;
                     if self.cursor > self.size {
                         self.view_offset = self.cursor - self.size;
@@ -276,7 +295,7 @@ where N: FromStr, N: Display {
                 if self.cursor < self.buf.len() {
                     self.buf.remove(self.cursor);
                     self.error = if self.buf.is_empty() { false }
-                                 else { self.buf.parse::<usize>().is_err() };
+                                 else { self.parse().is_none() };
                     return Ok(WidgetResult::Redraw);
                 } else {
                     return Ok(WidgetResult::Ignore);
@@ -290,7 +309,7 @@ where N: FromStr, N: Display {
                         self.view_offset -= 1;
                     }
                     self.error = if self.buf.is_empty() { false }
-                                 else { self.buf.parse::<usize>().is_err() };
+                                 else { self.parse().is_none() };
                     return Ok(WidgetResult::Redraw);
                 } else {
                     return Ok(WidgetResult::Ignore);
@@ -301,7 +320,7 @@ where N: FromStr, N: Display {
                 return Ok(WidgetResult::Redraw);
             }
             Input::Character('\n') => {
-                if let Ok(num) = self.buf.parse() {
+                if let Some(num) = self.parse() {
                     self.focused = false;
                     self.error   = false;
                     return Ok(WidgetResult::Value(num));
@@ -319,3 +338,63 @@ where N: FromStr, N: Display {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input<N>(text: &str) -> NumberInput<N>
+    where N: FromStr, N: Display, N: PartialOrd {
+        let mut input = NumberInput::new(20);
+        input.focus().unwrap();
+        for ch in text.chars() {
+            input.handle(Input::Character(ch)).unwrap();
+        }
+        input
+    }
+
+    #[test]
+    fn limits() {
+        let mut num = input::<i64>("256");
+        assert!(!num.error);
+        num.set_limits(0, 255);
+        assert!(num.error);
+        assert_eq!(num.handle(Input::Character('\n')).unwrap(), WidgetResult::Beep);
+        assert!(num.has_focus());
+
+        num.handle(Input::KeyBackspace).unwrap();
+        assert!(!num.error);
+        assert_eq!(num.handle(Input::Character('\n')).unwrap(), WidgetResult::Value(25));
+        assert!(!num.has_focus());
+    }
+
+    #[test]
+    fn delete_and_backspace_parse_the_number_type() {
+        // these used to be checked as unsigned integers
+        let mut num = input::<i64>("-123");
+        num.handle(Input::KeyBackspace).unwrap();
+        assert!(!num.error);
+        num.handle(Input::KeyHome).unwrap();
+        num.handle(Input::KeyRight).unwrap();
+        num.handle(Input::KeyDC).unwrap();
+        assert!(!num.error);
+        assert_eq!(num.buf, "-2");
+
+        let mut num = input::<f64>("1.5e");
+        assert!(num.error);
+        num.handle(Input::KeyBackspace).unwrap();
+        assert!(!num.error);
+        assert_eq!(num.handle(Input::Character('\n')).unwrap(), WidgetResult::Value(1.5));
+    }
+
+    #[test]
+    fn empty_is_no_error() {
+        let mut num = input::<u8>("x");
+        num.set_limits(1, 2);
+        assert!(!num.error);
+        let mut num = input::<u8>("7");
+        num.handle(Input::KeyBackspace).unwrap();
+        assert!(!num.error);
+        assert_eq!(num.handle(Input::Character('\n')).unwrap(), WidgetResult::Beep);
+    }
+}
@@ -0,0 +1,143 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+
+use crate::block_cache::BlockCache;
+
+const PAGE_SIZE: u64 = 64 * 1024;
+const MAX_PAGES: usize = 64;
+
+// Reads a file with pread() in aligned pages and keeps the most recently
+// used ones. For files that can't be mapped (e.g. some devices).
+pub struct Paged {
+    file:  File,
+    size:  u64,
+    pages: BlockCache<Vec<u8>>,
+}
+
+impl Paged {
+    pub fn new(file: File, size: u64) -> Self {
+        Self {
+            file,
+            size,
+            pages: BlockCache::new(PAGE_SIZE, MAX_PAGES),
+        }
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(Paged::new(self.file.try_clone()?, self.size))
+    }
+
+    #[inline]
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        self.pages.read(self.size, offset, buf, |page_offset, page_size| {
+            let mut data = vec![0; page_size];
+            self.file.read_exact_at(&mut data, page_offset)?;
+            Ok(data)
+        })
+    }
+
+    pub fn remap(&mut self, size: u64) {
+        self.pages.clear();
+        self.size = size;
+    }
+
+    // Forget cached pages, e.g. after writing to the file.
+    pub fn invalidate(&self) {
+        self.pages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn paged(name: &str, data: &[u8]) -> (TempFile, Paged) {
+        let path = std::env::temp_dir().join(format!("hox-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        let file = File::open(&path).unwrap();
+        (TempFile(path), Paged::new(file, data.len() as u64))
+    }
+
+    #[test]
+    fn reads_across_pages_and_at_eof() {
+        let size = PAGE_SIZE as usize * 2 + 100;
+        let data: Vec<u8> = (0..size).map(|index| (index % 251) as u8).collect();
+        let (_file, paged) = paged("paged", &data);
+
+        let mut buf = vec![0; 200];
+        let offset = PAGE_SIZE as usize - 100;
+        paged.read(offset as u64, &mut buf).unwrap();
+        assert_eq!(buf, data[offset..offset + 200]);
+
+        // spans all three pages
+        let mut buf = vec![0; size - 10];
+        paged.read(5, &mut buf).unwrap();
+        assert_eq!(buf, data[5..size - 5]);
+
+        // the last page is short
+        let mut buf = vec![0; 100];
+        paged.read(size as u64 - 100, &mut buf).unwrap();
+        assert_eq!(buf, data[size - 100..]);
+        paged.read(size as u64, &mut []).unwrap();
+
+        assert!(paged.read(size as u64 - 99, &mut buf).is_err());
+        assert!(paged.read(size as u64 + 1, &mut []).is_err());
+    }
+
+    #[test]
+    fn evicts_and_invalidates_pages() {
+        let size = PAGE_SIZE as usize * (MAX_PAGES + 1);
+        let data = vec![1; size];
+        let (file, paged) = paged("paged-evict", &data);
+
+        let mut buf = [0; 1];
+        for index in 0..=MAX_PAGES as u64 {
+            paged.read(index * PAGE_SIZE, &mut buf).unwrap();
+        }
+
+        // cached pages are stale until invalidated, page 0 got evicted
+        let writer = std::fs::OpenOptions::new().write(true).open(&file.0).unwrap();
+        writer.write_all_at(&[2], 0).unwrap();
+        writer.write_all_at(&[2], PAGE_SIZE).unwrap();
+        paged.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [1]);
+        paged.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [2]);
+        paged.invalidate();
+        paged.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [2]);
+    }
+}
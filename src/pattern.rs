@@ -0,0 +1,344 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cmp::{min, max};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::buffer::Buffer;
+use crate::hox::{Endian, get_f32, get_f64};
+use crate::result::Result;
+use crate::substring::Finder;
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+// Regular expression matches are only found completely if they aren't longer
+// than this, because the data is searched in overlapping blocks.
+pub const MAX_REGEX_MATCH: usize = 64 * 1024;
+
+// Shared between a search thread and the UI. The offset is where the search
+// currently is, searching stops soon after it got cancelled.
+pub struct SearchProgress {
+    offset:    AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl SearchProgress {
+    pub fn new(offset: u64) -> Self {
+        Self {
+            offset:    AtomicU64::new(offset),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn set_offset(&self, offset: u64) {
+        self.offset.store(offset, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// What is searched for. Finding returns the offset and the length of a match.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Bytes(Finder),
+    // value and mask, only the bits set in the mask have to match
+    Masked(Vec<u8>, Vec<u8>),
+    // a float of size bytes within min..=max
+    Float { size: usize, endian: Endian, min: f64, max: f64 },
+    // and the length of the longest match
+    Regex(Regex, usize),
+}
+
+impl Pattern {
+    #[inline]
+    pub fn bytes(bytes: Vec<u8>) -> Self {
+        Pattern::Bytes(Finder::new(bytes))
+    }
+
+    // Unicode is switched off so that . and \xNN match single bytes.
+    pub fn regex(source: &str) -> Result<Self> {
+        Self::regex_with_max_len(source, MAX_REGEX_MATCH)
+    }
+
+    // For generated regular expressions whose matches are known to be short.
+    pub fn regex_with_max_len(source: &str, max_len: usize) -> Result<Self> {
+        let regex = RegexBuilder::new(source)
+            .unicode(false)
+            .size_limit(1024 * 1024)
+            .build()?;
+        Ok(Pattern::Regex(regex, min(max_len, MAX_REGEX_MATCH)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Pattern::Bytes(finder) => finder.is_empty(),
+            Pattern::Masked(value, _) => value.is_empty(),
+            Pattern::Float { .. } => false,
+            Pattern::Regex(regex, _) => regex.as_str().is_empty(),
+        }
+    }
+
+    // The longest match that is found completely.
+    pub fn max_len(&self) -> usize {
+        match self {
+            Pattern::Bytes(finder)    => finder.len(),
+            Pattern::Masked(value, _) => value.len(),
+            Pattern::Float { size, .. } => *size,
+            Pattern::Regex(_, max_len) => *max_len,
+        }
+    }
+
+    // First match that starts at or after start_offset and ends at or before
+    // end_offset.
+    pub fn find(&self, buffer: &Buffer, start_offset: u64, end_offset: u64, progress: &SearchProgress) -> Option<(u64, u64)> {
+        match self {
+            Pattern::Bytes(finder) => {
+                find_fixed(buffer, finder.len(), start_offset, end_offset, progress, |data| finder.find(data))
+            }
+            Pattern::Masked(value, mask) => {
+                find_fixed(buffer, value.len(), start_offset, end_offset, progress, |data|
+                    data.windows(value.len()).position(|window| masked_eq(window, value, mask)))
+            }
+            Pattern::Float { size, endian, min, max } => {
+                find_fixed(buffer, *size, start_offset, end_offset, progress, |data|
+                    data.windows(*size).position(|window| float_in_range(window, *endian, *min, *max)))
+            }
+            Pattern::Regex(regex, max_len) => {
+                let size = min(end_offset, buffer.len());
+                let mut offset = start_offset;
+                while offset < size && !progress.is_cancelled() {
+                    progress.set_offset(offset);
+                    let read_end = min(offset + (BLOCK_SIZE + max_len) as u64, size);
+                    let data = buffer.read_range(offset, read_end);
+                    let limit = if read_end == size { data.len() } else { BLOCK_SIZE };
+                    for found in regex.find_iter(&data) {
+                        if found.start() >= limit {
+                            break;
+                        }
+                        if found.start() < found.end() {
+                            return Some((offset + found.start() as u64, found.len() as u64));
+                        }
+                    }
+                    offset += BLOCK_SIZE as u64;
+                }
+                None
+            }
+        }
+    }
+
+    // Last match that starts at or before start_offset and lies completely
+    // within min_offset..end_offset.
+    pub fn rfind(&self, buffer: &Buffer, min_offset: u64, start_offset: u64, end_offset: u64, progress: &SearchProgress) -> Option<(u64, u64)> {
+        match self {
+            Pattern::Bytes(finder) => {
+                rfind_fixed(buffer, finder.len(), min_offset, start_offset, end_offset, progress, |data| finder.rfind(data))
+            }
+            Pattern::Masked(value, mask) => {
+                rfind_fixed(buffer, value.len(), min_offset, start_offset, end_offset, progress, |data|
+                    data.windows(value.len()).rposition(|window| masked_eq(window, value, mask)))
+            }
+            Pattern::Float { size, endian, min, max } => {
+                rfind_fixed(buffer, *size, min_offset, start_offset, end_offset, progress, |data|
+                    data.windows(*size).rposition(|window| float_in_range(window, *endian, *min, *max)))
+            }
+            Pattern::Regex(regex, max_len) => {
+                let size = min(end_offset, buffer.len());
+                let mut block_end = min(start_offset.saturating_add(1), size);
+                while block_end > min_offset && !progress.is_cancelled() {
+                    progress.set_offset(block_end);
+                    let offset = max(block_end.saturating_sub(BLOCK_SIZE as u64), min_offset);
+                    let data = buffer.read_range(offset, min(block_end + *max_len as u64, size));
+                    let limit = (block_end - offset) as usize;
+                    let mut last = None;
+                    for found in regex.find_iter(&data) {
+                        if found.start() >= limit {
+                            break;
+                        }
+                        if found.start() < found.end() {
+                            last = Some((offset + found.start() as u64, found.len() as u64));
+                        }
+                    }
+                    if last.is_some() {
+                        return last;
+                    }
+                    block_end = offset;
+                }
+                None
+            }
+        }
+    }
+
+    // Collects the matches that lie completely within start_offset..end_offset,
+    // at most limit of them. Returns whether there were more.
+    pub fn find_all(&self, buffer: &Buffer, start_offset: u64, end_offset: u64, limit: usize, progress: &SearchProgress, matches: &mut Vec<(u64, u64)>) -> bool {
+        let overlapping = !matches!(self, Pattern::Regex(..));
+        let overlap = self.max_len().saturating_sub(1);
+        let size = min(end_offset, buffer.len());
+        let mut next_start = 0;
+        let mut more = false;
+        let mut offset = start_offset;
+        while offset < size && !more && !progress.is_cancelled() {
+            progress.set_offset(offset);
+            let read_end = min(offset + (BLOCK_SIZE + overlap) as u64, size);
+            let data = buffer.read_range(offset, read_end);
+            let block_end = if read_end == size { data.len() } else { BLOCK_SIZE };
+            self.for_each_match(&data, |start, end| {
+                let match_offset = offset + start as u64;
+                // the next block finds matches that start in the overlap
+                if more || start >= block_end || match_offset < next_start {
+                    return;
+                }
+                if matches.len() >= limit {
+                    more = true;
+                    return;
+                }
+                matches.push((match_offset, (end - start) as u64));
+                next_start = if overlapping { match_offset + 1 } else { offset + end as u64 };
+            });
+            offset += BLOCK_SIZE as u64;
+        }
+        more
+    }
+
+    // Length of the match that starts at offset, if there is one.
+    pub fn match_at(&self, buffer: &Buffer, offset: u64) -> Option<u64> {
+        let data = buffer.read_range(offset, offset.saturating_add(self.max_len() as u64));
+        let mut found = None;
+        self.for_each_match(&data, |start, end| {
+            if start == 0 && found.is_none() {
+                found = Some(end as u64);
+            }
+        });
+        found
+    }
+
+    // Calls found(start, end) for every match in data. Byte matches may
+    // overlap, regular expression matches don't.
+    pub fn for_each_match(&self, data: &[u8], mut found: impl FnMut(usize, usize)) {
+        match self {
+            Pattern::Bytes(finder) => {
+                for index in finder.find_iter(data) {
+                    found(index, index + finder.len());
+                }
+            }
+            Pattern::Masked(value, mask) => {
+                if value.is_empty() {
+                    return;
+                }
+                for (index, window) in data.windows(value.len()).enumerate() {
+                    if masked_eq(window, value, mask) {
+                        found(index, index + value.len());
+                    }
+                }
+            }
+            Pattern::Float { size, endian, min, max } => {
+                for (index, window) in data.windows(*size).enumerate() {
+                    if float_in_range(window, *endian, *min, *max) {
+                        found(index, index + size);
+                    }
+                }
+            }
+            Pattern::Regex(regex, _) => {
+                for item in regex.find_iter(data) {
+                    if item.start() < item.end() {
+                        found(item.start(), item.end());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn masked_eq(data: &[u8], value: &[u8], mask: &[u8]) -> bool {
+    data.iter().zip(value).zip(mask).all(|((byte, value), mask)| byte & mask == *value)
+}
+
+#[inline]
+fn float_in_range(data: &[u8], endian: Endian, min: f64, max: f64) -> bool {
+    let value = if data.len() == 4 {
+        get_f32(data, 0, endian).map(|value| value as f64)
+    } else {
+        get_f64(data, 0, endian)
+    };
+    // NaN never matches
+    value.map(|value| value >= min && value <= max).unwrap_or(false)
+}
+
+// The data is read in overlapping blocks, find_in/rfind_in return the index of
+// the first/last match in a block.
+fn find_fixed(buffer: &Buffer, len: usize, start_offset: u64, end_offset: u64, progress: &SearchProgress, find_in: impl Fn(&[u8]) -> Option<usize>) -> Option<(u64, u64)> {
+    if len == 0 {
+        return None;
+    }
+
+    let size = min(end_offset, buffer.len());
+    let mut buf = vec![0; BLOCK_SIZE + len - 1];
+    let mut offset = start_offset;
+    while offset + len as u64 <= size && !progress.is_cancelled() {
+        progress.set_offset(offset);
+        let read_len = min(buf.len() as u64, size - offset) as usize;
+        let count = buffer.read(offset, &mut buf[..read_len]);
+        if count < len {
+            break;
+        }
+        if let Some(index) = find_in(&buf[..count]) {
+            return Some((offset + index as u64, len as u64));
+        }
+        offset += (count + 1 - len) as u64;
+    }
+
+    None
+}
+
+fn rfind_fixed(buffer: &Buffer, len: usize, min_offset: u64, start_offset: u64, end_offset: u64, progress: &SearchProgress, rfind_in: impl Fn(&[u8]) -> Option<usize>) -> Option<(u64, u64)> {
+    let size = min(end_offset, buffer.len());
+    if len == 0 || min_offset.saturating_add(len as u64) > size {
+        return None;
+    }
+
+    let mut buf = vec![0; BLOCK_SIZE + len - 1];
+    let mut block_end = min(start_offset.saturating_add(len as u64), size);
+    while block_end >= min_offset + len as u64 && !progress.is_cancelled() {
+        progress.set_offset(block_end);
+        let offset = max(block_end.saturating_sub(buf.len() as u64), min_offset);
+        let count = buffer.read(offset, &mut buf[..(block_end - offset) as usize]);
+        if let Some(index) = rfind_in(&buf[..count]) {
+            return Some((offset + index as u64, len as u64));
+        }
+        if offset == min_offset {
+            break;
+        }
+        block_end = offset + len as u64 - 1;
+    }
+
+    None
+}
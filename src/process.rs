@@ -0,0 +1,203 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::cmp::min;
+use std::os::unix::fs::FileExt;
+
+use crate::result::{Result, Error};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Region {
+    pub start: u64,
+    pub end:   u64,
+    pub perms: String,
+    pub name:  String,
+}
+
+impl Region {
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+// The readable regions of a process' address space read via /proc/PID/mem.
+// Offsets are the regions put one after another, skipping the gaps between
+// them.
+pub struct ProcessMemory {
+    file:    File,
+    regions: Vec<Region>,
+    starts:  Vec<u64>,
+    size:    u64,
+}
+
+impl ProcessMemory {
+    pub fn open(pid: u32) -> Result<Self> {
+        let maps_path = format!("/proc/{}/maps", pid);
+        let maps = std::fs::read_to_string(&maps_path).map_err(|error| Error::io_with_path(error, &maps_path))?;
+
+        let mut regions = Vec::new();
+        for line in maps.lines() {
+            let region = parse_region(line).ok_or_else(||
+                Error::message(format!("illegal line in {}: {:?}", maps_path, line)))?;
+
+            // [vvar] can't be read through /proc/PID/mem
+            if region.perms.starts_with('r') && !region.name.starts_with("[vvar") && region.end > region.start {
+                regions.push(region);
+            }
+        }
+
+        let mem_path = format!("/proc/{}/mem", pid);
+        let file = File::open(&mem_path).map_err(|error| Error::io_with_path(error, &mem_path))?;
+
+        let mut starts = Vec::with_capacity(regions.len());
+        let mut size = 0;
+        for region in &regions {
+            starts.push(size);
+            size += region.len();
+        }
+
+        Ok(Self { file, regions, starts, size })
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(Self {
+            file:    self.file.try_clone()?,
+            regions: self.regions.clone(),
+            starts:  self.starts.clone(),
+            size:    self.size,
+        })
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    #[inline]
+    pub fn region_offset(&self, index: usize) -> u64 {
+        self.starts[index]
+    }
+
+    pub fn region_index(&self, offset: u64) -> Option<usize> {
+        if offset >= self.size {
+            return None;
+        }
+        Some(self.starts.partition_point(|start| *start <= offset) - 1)
+    }
+
+    pub fn address(&self, offset: u64) -> u64 {
+        if let Some(index) = self.region_index(offset) {
+            self.regions[index].start + offset - self.starts[index]
+        } else if let Some(region) = self.regions.last() {
+            region.end + offset - self.size
+        } else {
+            offset
+        }
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        if offset > self.size || buf.len() as u64 > self.size - offset {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let index = self.region_index(offset + pos as u64).unwrap();
+            let region = &self.regions[index];
+            let skip  = offset + pos as u64 - self.starts[index];
+            let count = min(region.len() - skip, (buf.len() - pos) as u64) as usize;
+            self.file.read_exact_at(&mut buf[pos..pos + count], region.start + skip)?;
+            pos += count;
+        }
+
+        Ok(())
+    }
+}
+
+// 7f7d732ae000-7f7d732d4000 r--p 00000000 fe:00 395379    /usr/lib/libc.so.6
+fn parse_region(line: &str) -> Option<Region> {
+    let mut fields = line.splitn(6, ' ');
+    let range = fields.next()?;
+    let perms = fields.next()?;
+    let _offset = fields.next()?;
+    let _device = fields.next()?;
+    let _inode  = fields.next()?;
+    let name = fields.next().unwrap_or("").trim_start();
+
+    let (start, end) = range.split_once('-')?;
+    let start = u64::from_str_radix(start, 16).ok()?;
+    let end   = u64::from_str_radix(end,   16).ok()?;
+
+    Some(Region {
+        start,
+        end,
+        perms: perms.to_owned(),
+        name:  name.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(start: u64, end: u64, perms: &str, name: &str) -> Option<Region> {
+        Some(Region { start, end, perms: perms.to_owned(), name: name.to_owned() })
+    }
+
+    #[test]
+    fn parse_lines() {
+        let cases = [
+            ("7f7d732ae000-7f7d732d4000 r--p 00000000 fe:00 395379                     /usr/lib/libc.so.6",
+             region(0x7f7d732ae000, 0x7f7d732d4000, "r--p", "/usr/lib/libc.so.6")),
+            ("7f7d732d4000-7f7d732d6000 rw-p 00000000 00:00 0 ",
+             region(0x7f7d732d4000, 0x7f7d732d6000, "rw-p", "")),
+            ("7f7d732d6000-7f7d732d8000 rw-p 00000000 00:00 0",
+             region(0x7f7d732d6000, 0x7f7d732d8000, "rw-p", "")),
+            ("55d0c3a5f000-55d0c3a80000 rw-p 00000000 00:00 0                          [heap]",
+             region(0x55d0c3a5f000, 0x55d0c3a80000, "rw-p", "[heap]")),
+            ("7ffc6a1f1000-7ffc6a212000 rw-p 00000000 00:00 0                          [stack]",
+             region(0x7ffc6a1f1000, 0x7ffc6a212000, "rw-p", "[stack]")),
+            ("ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0                  [vsyscall]",
+             region(0xffffffffff600000, 0xffffffffff601000, "--xp", "[vsyscall]")),
+            ("7f1e00000000-7f1e00021000 r--s 00001000 08:01 1234                       /tmp/my file (deleted)",
+             region(0x7f1e00000000, 0x7f1e00021000, "r--s", "/tmp/my file (deleted)")),
+            ("", None),
+            ("garbage", None),
+            ("7f7d732ae000 r--p 00000000 fe:00 395379", None),
+            ("7f7d732ae000-xyz r--p 00000000 fe:00 395379", None),
+            ("7f7d732ae000-7f7d732d4000 r--p 00000000", None),
+            ("10000000000000000-10000000000000001 r--p 00000000 00:00 0", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_region(line), expected, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn open_own_process() {
+        let memory = ProcessMemory::open(std::process::id()).unwrap();
+        assert!(!memory.regions().is_empty());
+        assert!(memory.regions().iter().all(|region| region.perms.starts_with('r') && !region.name.starts_with("[vvar")));
+        assert_eq!(memory.size(), memory.regions().iter().map(Region::len).sum::<u64>());
+    }
+}
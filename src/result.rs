@@ -147,4 +147,19 @@ impl From<std::str::Utf8Error> for Error {
     }
 }
 
+impl From<regex::Error> for Error {
+    fn from(error: regex::Error) -> Self {
+        // syntax errors are multi-line with the reason at the end
+        let message = match &error {
+            regex::Error::Syntax(syntax) => format!("illegal regular expression: {}",
+                syntax.lines().last().unwrap_or("").trim_start_matches("error: ")),
+            _ => format!("{}", error),
+        };
+        Error {
+            error_type: ErrorType::Message(message),
+            path: None,
+        }
+    }
+}
+
 pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,252 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cmp::{min, max};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::buffer::Buffer;
+use crate::pattern::{Pattern, SearchProgress};
+use crate::result::{Result, Error};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_sigint(_signum: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub enum SearchResult {
+    // offset and length of the match, and whether the search wrapped around
+    Found(Option<(u64, u64)>, bool),
+    // offsets and lengths of all matches, and whether there were more
+    All(Vec<(u64, u64)>, bool),
+}
+
+// A search running in its own thread on a snapshot of the buffer. While it
+// runs Ctrl+C cancels it instead of killing the program.
+pub struct SearchJob {
+    thread:       JoinHandle<SearchResult>,
+    progress:     Arc<SearchProgress>,
+    forward:      bool,
+    start_offset: u64,
+    range:        (u64, u64),
+    wrap:         bool,
+    old_sigint:   libc::sigaction,
+}
+
+impl SearchJob {
+    // Searches for a match within range, forward from start_offset or backward
+    // from before start_offset. With wrap the search continues at the other
+    // end of the range.
+    pub fn start(buffer: &Buffer, pattern: &Pattern, start_offset: u64, forward: bool, range: (u64, u64), wrap: bool) -> Result<Self> {
+        let pattern = pattern.clone();
+        let (range_start, range_end) = range;
+        Self::spawn(buffer, start_offset, forward, range, wrap, move |snapshot, progress| {
+            let found = if forward {
+                pattern.find(snapshot, max(start_offset, range_start), range_end, progress)
+            } else if start_offset > range_start {
+                pattern.rfind(snapshot, range_start, start_offset - 1, range_end, progress)
+            } else {
+                None
+            };
+
+            if found.is_some() || !wrap || progress.is_cancelled() {
+                return SearchResult::Found(found, false);
+            }
+
+            let found = if forward {
+                pattern.find(snapshot, range_start, range_end, progress)
+            } else {
+                pattern.rfind(snapshot, range_start, u64::MAX, range_end, progress)
+            };
+            let wrapped = found.is_some();
+            SearchResult::Found(found, wrapped)
+        })
+    }
+
+    pub fn find_all(buffer: &Buffer, pattern: &Pattern, start_offset: u64, end_offset: u64, limit: usize) -> Result<Self> {
+        let pattern = pattern.clone();
+        Self::spawn(buffer, start_offset, true, (start_offset, end_offset), false, move |snapshot, progress| {
+            let mut matches = Vec::new();
+            let more = pattern.find_all(snapshot, start_offset, end_offset, limit, progress, &mut matches);
+            SearchResult::All(matches, more)
+        })
+    }
+
+    fn spawn(buffer: &Buffer, start_offset: u64, forward: bool, range: (u64, u64), wrap: bool, search: impl FnOnce(&Buffer, &SearchProgress) -> SearchResult + Send + 'static) -> Result<Self> {
+        let snapshot = buffer.snapshot()?;
+        let range_end = min(range.1, snapshot.len());
+        let range     = (min(range.0, range_end), range_end);
+        let progress = Arc::new(SearchProgress::new(start_offset));
+        let thread_progress = progress.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("search".to_owned())
+            .spawn(move || search(&snapshot, &thread_progress))?;
+
+        INTERRUPTED.store(false, Ordering::Relaxed);
+        let old_sigint = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_sigint as *const () as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut old_action: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, &action, &mut old_action);
+            old_action
+        };
+
+        Ok(Self {
+            thread,
+            progress,
+            forward,
+            start_offset,
+            range,
+            wrap,
+            old_sigint,
+        })
+    }
+
+    #[inline]
+    pub fn is_forward(&self) -> bool {
+        self.forward
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    #[inline]
+    pub fn is_interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::Relaxed)
+    }
+
+    // Most searches are over before anyone could see a progress display.
+    pub fn wait_for(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while !self.thread.is_finished() {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.progress.offset()
+    }
+
+    pub fn percent(&self) -> u64 {
+        let offset = self.offset();
+        let (range_start, range_end) = self.range;
+        let start_offset = self.start_offset.clamp(range_start, range_end);
+        // after wrapping around the offset is on the other side of the start
+        let (done, total) = if self.forward {
+            let done = if offset >= start_offset {
+                offset - start_offset
+            } else {
+                (range_end - start_offset) + offset.saturating_sub(range_start)
+            };
+            (done, if self.wrap { range_end - range_start } else { range_end - start_offset })
+        } else {
+            let done = if offset <= start_offset {
+                start_offset - offset
+            } else {
+                (start_offset - range_start) + range_end.saturating_sub(offset)
+            };
+            (done, if self.wrap { range_end - range_start } else { start_offset - range_start })
+        };
+
+        if total == 0 {
+            100
+        } else {
+            (min(done, total) as u128 * 100 / total as u128) as u64
+        }
+    }
+
+    pub fn join(self) -> Result<SearchResult> {
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.old_sigint, std::ptr::null_mut());
+        }
+        self.thread.join().map_err(|_| Error::message("search thread crashed"))
+    }
+
+    pub fn cancel(self) {
+        self.progress.cancel();
+        let _ = self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backing::Backing;
+
+    // "ab" at 0, 3 and 6
+    const DATA: &[u8] = b"ab.ab.ab..";
+
+    fn memory(data: &[u8]) -> Buffer {
+        let mut buffer = Buffer::new(Backing::Empty(std::fs::File::open("/dev/null").unwrap()));
+        buffer.replace(0, 0, data);
+        buffer
+    }
+
+    fn search(start_offset: u64, forward: bool, range: (u64, u64), wrap: bool) -> (Option<(u64, u64)>, bool) {
+        let pattern = Pattern::bytes(b"ab".to_vec());
+        match SearchJob::start(&memory(DATA), &pattern, start_offset, forward, range, wrap).unwrap().join().unwrap() {
+            SearchResult::Found(found, wrapped) => (found, wrapped),
+            SearchResult::All(..) => panic!("expected a single match"),
+        }
+    }
+
+    #[test]
+    fn search_within_range() {
+        assert_eq!(search(0, true, (2, 8), false), (Some((3, 2)), false));
+        assert_eq!(search(4, true, (2, 8), false), (Some((6, 2)), false));
+        assert_eq!(search(7, false, (2, 8), false), (Some((6, 2)), false));
+        assert_eq!(search(6, false, (2, 8), false), (Some((3, 2)), false));
+        assert_eq!(search(7, true, (2, 8), false), (None, false));
+        assert_eq!(search(3, false, (2, 8), false), (None, false));
+    }
+
+    #[test]
+    fn search_wraps_at_each_end() {
+        assert_eq!(search(7, true, (2, 8), true), (Some((3, 2)), true));
+        assert_eq!(search(3, false, (2, 8), true), (Some((6, 2)), true));
+        assert_eq!(search(2, false, (2, 8), true), (Some((6, 2)), true));
+    }
+
+    #[test]
+    fn not_found_within_range() {
+        // the matches at 0 and 3 each reach past one end of the range
+        assert_eq!(search(1, true, (1, 4), true), (None, false));
+        assert_eq!(search(4, false, (1, 4), true), (None, false));
+    }
+
+    #[test]
+    fn find_all_within_range() {
+        let pattern = Pattern::bytes(b"ab".to_vec());
+        let find_all = |start, end, limit| match SearchJob::find_all(&memory(DATA), &pattern, start, end, limit).unwrap().join().unwrap() {
+            SearchResult::All(matches, more) => (matches, more),
+            SearchResult::Found(..) => panic!("expected all matches"),
+        };
+        assert_eq!(find_all(2, 8, 10), (vec![(3, 2), (6, 2)], false));
+        assert_eq!(find_all(1, 7, 10), (vec![(3, 2)], false));
+        assert_eq!(find_all(0, 10, 2), (vec![(0, 2), (3, 2)], true));
+    }
+}
@@ -22,7 +22,8 @@ use pancurses_result::{Window, Point, Input, ColorPair, Dimension};
 use crate::input_widget::{InputWidget, WidgetResult};
 use crate::result::{Result, Error};
 use crate::consts::*;
-use crate::hox::{Endian, is_printable_ascii};
+use crate::hox::{Endian, is_printable_ascii, get_f32, get_f64};
+use crate::pattern::Pattern;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum IntSize {
@@ -43,6 +44,28 @@ impl IntSize {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FloatSize {
+    F32,
+    F64,
+}
+
+impl FloatSize {
+    pub fn next(&self) -> Self {
+        match self {
+            FloatSize::F64 => FloatSize::F32,
+            FloatSize::F32 => FloatSize::F64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            FloatSize::F32 => 4,
+            FloatSize::F64 => 8,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Sign {
     Signed,
@@ -74,18 +97,157 @@ impl Sign {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub fn next(&self) -> Self {
+        match self {
+            TextEncoding::Utf8    => TextEncoding::Utf16Le,
+            TextEncoding::Utf16Le => TextEncoding::Utf16Be,
+            TextEncoding::Utf16Be => TextEncoding::Latin1,
+            TextEncoding::Latin1  => TextEncoding::Utf8,
+        }
+    }
+
+    pub fn encode(&self, ch: char, data: &mut Vec<u8>) -> Result<()> {
+        match self {
+            TextEncoding::Utf8 => {
+                let mut buf = [0; 4];
+                data.extend(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            TextEncoding::Utf16Le => {
+                let mut buf = [0; 2];
+                for unit in ch.encode_utf16(&mut buf) {
+                    data.extend(&unit.to_le_bytes());
+                }
+            }
+            TextEncoding::Utf16Be => {
+                let mut buf = [0; 2];
+                for unit in ch.encode_utf16(&mut buf) {
+                    data.extend(&unit.to_be_bytes());
+                }
+            }
+            TextEncoding::Latin1 => {
+                if ch as u32 > 0xFF {
+                    return Err(Error::message(format!("character not in Latin-1: {:?}", ch)));
+                }
+                data.push(ch as u8);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn decode(&self, input: &[u8]) -> Result<String> {
+        match self {
+            TextEncoding::Utf8 => Ok(std::str::from_utf8(input)?.to_owned()),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                if input.len() & 1 != 0 {
+                    return Err(Error::message("odd number of bytes for UTF-16"));
+                }
+                let units: Vec<u16> = input.chunks(2).map(|pair| {
+                    if *self == TextEncoding::Utf16Le {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                }).collect();
+                String::from_utf16(&units).map_err(|error| Error::message(format!("{}", error)))
+            }
+            TextEncoding::Latin1 => Ok(input.iter().map(|byte| *byte as char).collect()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Case {
+    Sensitive,
+    IgnoreAscii,
+    IgnoreUnicode,
+}
+
+impl Case {
+    pub fn next(&self) -> Self {
+        match self {
+            Case::Sensitive     => Case::IgnoreAscii,
+            Case::IgnoreAscii   => Case::IgnoreUnicode,
+            Case::IgnoreUnicode => Case::Sensitive,
+        }
+    }
+
+    // All the ways ch can be written, ch itself first.
+    pub fn variants(&self, ch: char) -> Vec<char> {
+        let mut variants = vec![ch];
+        match self {
+            Case::Sensitive => {}
+            Case::IgnoreAscii => {
+                for other in [ch.to_ascii_lowercase(), ch.to_ascii_uppercase()] {
+                    if !variants.contains(&other) {
+                        variants.push(other);
+                    }
+                }
+            }
+            Case::IgnoreUnicode => {
+                // mappings to several characters (ß -> SS) are left out
+                let mut index = 0;
+                while index < variants.len() && index < 4 {
+                    let ch = variants[index];
+                    let mut lower = ch.to_lowercase();
+                    let mut upper = ch.to_uppercase();
+                    for other in [lower.next().filter(|_| lower.next().is_none()), upper.next().filter(|_| upper.next().is_none())].into_iter().flatten() {
+                        if !variants.contains(&other) {
+                            variants.push(other);
+                        }
+                    }
+                    index += 1;
+                }
+            }
+        }
+        variants
+    }
+}
+
+// width of the mode label next to the input
+const MODE_WIDTH: usize = 12;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum SearchMode {
-    String,
+    String(TextEncoding, Case),
     Binary,
+    Regex,
     Integer(IntSize, Sign, Endian),
+    Float(FloatSize, Endian),
 }
 
 impl Display for SearchMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SearchMode::String => "Text".fmt(f),
+            SearchMode::String(encoding, case) => {
+                let encoding = match encoding {
+                    TextEncoding::Utf8    => "UTF-8",
+                    TextEncoding::Utf16Le => "UTF-16LE",
+                    TextEncoding::Utf16Be => "UTF-16BE",
+                    TextEncoding::Latin1  => "Latin-1",
+                };
+                let case = match case {
+                    Case::Sensitive     => "",
+                    Case::IgnoreAscii   => " i",
+                    Case::IgnoreUnicode => " ui",
+                };
+                let label = format!("{}{}", encoding, case);
+                if let Some(width) = f.width() {
+                    write!(f, "{:<width$}", label, width = width)
+                } else {
+                    f.write_str(&label)
+                }
+            }
             SearchMode::Binary => "Binary".fmt(f),
+            SearchMode::Regex  => "Regex".fmt(f),
             SearchMode::Integer(size, sign, endian) => {
                 match sign {
                     Sign::Signed   => f.write_str("Int  ")?,
@@ -114,15 +276,83 @@ impl Display for SearchMode {
 
                 Ok(())
             }
+            SearchMode::Float(size, endian) => {
+                let label = format!("Float {} {}",
+                    match size {
+                        FloatSize::F32 => "32",
+                        FloatSize::F64 => "64",
+                    },
+                    match endian {
+                        Endian::Little => "LE",
+                        Endian::Big    => "BE",
+                    });
+                if let Some(width) = f.width() {
+                    write!(f, "{:<width$}", label, width = width)
+                } else {
+                    f.write_str(&label)
+                }
+            }
         }
     }
 }
 
 impl SearchMode {
-    #[allow(unused)]
+    pub const TEXT: SearchMode = SearchMode::String(TextEncoding::Utf8, Case::Sensitive);
+
+    // Inverse of the Display label.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let words: Vec<&str> = label.split_whitespace().collect();
+        let endian = |word: &str| match word {
+            "LE" => Some(Endian::Little),
+            "BE" => Some(Endian::Big),
+            _    => None,
+        };
+
+        match &words[..] {
+            ["Binary"] => Some(SearchMode::Binary),
+            ["Regex"]  => Some(SearchMode::Regex),
+            [sign @ ("Int" | "UInt"), size, order] => {
+                let sign = if *sign == "Int" { Sign::Signed } else { Sign::Unsigned };
+                let size = match *size {
+                    "8"  => IntSize::I8,
+                    "16" => IntSize::I16,
+                    "32" => IntSize::I32,
+                    "64" => IntSize::I64,
+                    _    => return None,
+                };
+                Some(SearchMode::Integer(size, sign, endian(order)?))
+            }
+            ["Float", size, order] => {
+                let size = match *size {
+                    "32" => FloatSize::F32,
+                    "64" => FloatSize::F64,
+                    _    => return None,
+                };
+                Some(SearchMode::Float(size, endian(order)?))
+            }
+            [encoding, case @ ..] => {
+                let encoding = match *encoding {
+                    "UTF-8"    => TextEncoding::Utf8,
+                    "UTF-16LE" => TextEncoding::Utf16Le,
+                    "UTF-16BE" => TextEncoding::Utf16Be,
+                    "Latin-1"  => TextEncoding::Latin1,
+                    _          => return None,
+                };
+                let case = match case {
+                    []     => Case::Sensitive,
+                    ["i"]  => Case::IgnoreAscii,
+                    ["ui"] => Case::IgnoreUnicode,
+                    _      => return None,
+                };
+                Some(SearchMode::String(encoding, case))
+            }
+            [] => None,
+        }
+    }
+
     pub fn is_string(&self) -> bool {
         match self {
-            SearchMode::String => true,
+            SearchMode::String(_, _) => true,
             _ => false,
         }
     }
@@ -135,6 +365,10 @@ impl SearchMode {
         }
     }
 
+    pub fn is_regex(&self) -> bool {
+        matches!(self, SearchMode::Regex)
+    }
+
     #[allow(unused)]
     pub fn is_integer(&self) -> bool {
         match self {
@@ -143,63 +377,38 @@ impl SearchMode {
         }
     }
 
+    #[allow(unused)]
+    pub fn is_float(&self) -> bool {
+        matches!(self, SearchMode::Float(_, _))
+    }
+
     pub fn parse(&self, input: &[char]) -> Result<Vec<u8>> {
         let mut data = Vec::new();
         match self {
-            SearchMode::String => {
-                let mut buf = [0; 4];
+            SearchMode::String(encoding, _) => {
                 for ch in input {
-                    let count = ch.encode_utf8(&mut buf).len();
-                    data.extend(&buf[..count]);
+                    encoding.encode(*ch, &mut data)?;
                 }
             }
             SearchMode::Binary => {
-                let mut iter = input.iter();
-                loop {
-                    if let Some(ch) = iter.next() {
-                        let ch = *ch;
-                        let mut byte = if ch >= 'a' && ch <= 'f' {
-                            ch as u8 - 'a' as u8 + 10
-                        } else if ch >= 'A' && ch <= 'F' {
-                            ch as u8 - 'A' as u8 + 10
-                        } else if ch >= '0' && ch <= '9' {
-                            ch as u8 - '0' as u8
-                        } else {
-                            return Err(Error::message(format!(
-                                "illegal byte in hex string: {:?}",
-                                input.iter().collect::<String>())));
-                        };
-                        if let Some(ch) = iter.next() {
-                            byte <<= 4;
-                            let ch = *ch;
-                            byte |= if ch >= 'a' && ch <= 'f' {
-                                ch as u8 - 'a' as u8 + 10
-                            } else if ch >= 'A' && ch <= 'F' {
-                                ch as u8 - 'A' as u8 + 10
-                            } else if ch >= '0' && ch <= '9' {
-                                ch as u8 - '0' as u8
-                            } else {
-                                return Err(Error::message(format!(
-                                    "illegal byte in hex string: {:?}",
-                                    input.iter().collect::<String>())));
-                            };
-                            data.push(byte);
-                            match iter.next() {
-                                Some(' ') => {},
-                                Some(_) => {
-                                    return Err(Error::message(format!(
-                                        "illegal byte in hex string: {:?}",
-                                        input.iter().collect::<String>())));
-                                }
-                                None => break,
-                            }
-                        } else {
-                            data.push(byte);
-                            break;
-                        }
-                    } else {
-                        break;
-                    }
+                let (value, mask) = parse_hex_pattern(input)?;
+                if mask.iter().any(|bits| *bits != 0xFF) {
+                    return Err(Error::message("wildcards don't match fixed bytes"));
+                }
+                data = value;
+            }
+
+            SearchMode::Regex => {
+                return Err(Error::message("a regular expression doesn't match fixed bytes"));
+            }
+
+            SearchMode::Float(size, endian) => {
+                let (value, _) = parse_float(input)?;
+                match (size, endian) {
+                    (FloatSize::F32, Endian::Little) => data.extend(&(value as f32).to_le_bytes()),
+                    (FloatSize::F32, Endian::Big)    => data.extend(&(value as f32).to_be_bytes()),
+                    (FloatSize::F64, Endian::Little) => data.extend(&value.to_le_bytes()),
+                    (FloatSize::F64, Endian::Big)    => data.extend(&value.to_be_bytes()),
                 }
             }
 
@@ -288,14 +497,36 @@ impl SearchMode {
                 }
                 Ok(buf)
             }
-            SearchMode::String => {
-                Ok(std::str::from_utf8(input)?.to_owned())
+            SearchMode::String(encoding, _) => {
+                encoding.decode(input)
+            }
+            SearchMode::Regex => {
+                let mut buf = String::new();
+                for byte in input {
+                    if is_printable_ascii(*byte) {
+                        buf.push_str(&regex::escape(&(*byte as char).to_string()));
+                    } else {
+                        write!(buf, "\\x{:02X}", byte).unwrap();
+                    }
+                }
+                Ok(buf)
             }
 
-            SearchMode::Integer(_, _, _) if input.is_empty() => {
+            SearchMode::Integer(_, _, _) | SearchMode::Float(_, _) if input.is_empty() => {
                 Ok("0".to_owned())
             }
 
+            SearchMode::Float(FloatSize::F32, endian) => {
+                get_f32(input, 0, *endian)
+                    .map(|value| format!("{}", value))
+                    .ok_or_else(|| Error::message("not enough bytes"))
+            }
+            SearchMode::Float(FloatSize::F64, endian) => {
+                get_f64(input, 0, *endian)
+                    .map(|value| format!("{}", value))
+                    .ok_or_else(|| Error::message("not enough bytes"))
+            }
+
             SearchMode::Integer(IntSize::I8, Sign::Unsigned, _) => {
                 Ok(format!("{}", input[0]))
             }
@@ -392,19 +623,84 @@ impl SearchMode {
         }
     }
 
+    // Regex and hex strings with wildcards are not made of fixed bytes, so
+    // this is where they are compiled.
+    pub fn pattern(&self, input: &[char]) -> Result<Pattern> {
+        match self {
+            SearchMode::Regex => Pattern::regex(&input.iter().collect::<String>()),
+            SearchMode::String(encoding, case) if *case != Case::Sensitive => {
+                // every character becomes an alternation of its encoded variants
+                let mut source = String::new();
+                let mut data = Vec::new();
+                let mut max_len = 0;
+                for ch in input {
+                    let variants = case.variants(*ch);
+                    source.push_str("(?:");
+                    let mut first = true;
+                    let mut longest = 0;
+                    for variant in variants {
+                        data.clear();
+                        if encoding.encode(variant, &mut data).is_err() {
+                            continue;
+                        }
+                        if !first {
+                            source.push('|');
+                        }
+                        first = false;
+                        longest = longest.max(data.len());
+                        for byte in &data {
+                            write!(source, "\\x{:02X}", byte).unwrap();
+                        }
+                    }
+                    if first {
+                        // the character itself can't be encoded
+                        encoding.encode(*ch, &mut data)?;
+                    }
+                    source.push(')');
+                    max_len += longest;
+                }
+                Pattern::regex_with_max_len(&source, max_len)
+            }
+            SearchMode::Float(size, endian) => {
+                match parse_float(input)? {
+                    (value, Some(epsilon)) => Ok(Pattern::Float {
+                        size:   size.len(),
+                        endian: *endian,
+                        min:    value - epsilon,
+                        max:    value + epsilon,
+                    }),
+                    (_, None) => Ok(Pattern::bytes(self.parse(input)?)),
+                }
+            }
+            SearchMode::Binary => {
+                let (value, mask) = parse_hex_pattern(input)?;
+                if mask.iter().all(|bits| *bits == 0xFF) {
+                    Ok(Pattern::bytes(value))
+                } else {
+                    Ok(Pattern::Masked(value, mask))
+                }
+            }
+            _ => Ok(Pattern::bytes(self.parse(input)?)),
+        }
+    }
+
     pub fn next_major(&self) -> Self {
         match self {
-            SearchMode::String => SearchMode::Binary,
-            SearchMode::Binary => SearchMode::Integer(IntSize::I64, Sign::Signed, Endian::Little),
-            SearchMode::Integer(_, _, _) => SearchMode::String,
+            SearchMode::String(_, _) => SearchMode::Binary,
+            SearchMode::Binary => SearchMode::Regex,
+            SearchMode::Regex  => SearchMode::Integer(IntSize::I64, Sign::Signed, Endian::Little),
+            SearchMode::Integer(_, _, _) => SearchMode::Float(FloatSize::F64, Endian::Little),
+            SearchMode::Float(_, _) => SearchMode::TEXT,
         }
     }
 
     pub fn prev_major(&self) -> Self {
         match self {
-            SearchMode::String => SearchMode::Integer(IntSize::I64, Sign::Signed, Endian::Little),
-            SearchMode::Binary => SearchMode::String,
-            SearchMode::Integer(_, _, _) => SearchMode::Binary,
+            SearchMode::String(_, _) => SearchMode::Float(FloatSize::F64, Endian::Little),
+            SearchMode::Float(_, _) => SearchMode::Integer(IntSize::I64, Sign::Signed, Endian::Little),
+            SearchMode::Binary => SearchMode::TEXT,
+            SearchMode::Regex  => SearchMode::Binary,
+            SearchMode::Integer(_, _, _) => SearchMode::Regex,
         }
     }
 
@@ -413,6 +709,12 @@ impl SearchMode {
             SearchMode::Integer(size, sign, endian) => {
                 SearchMode::Integer(size.next(), *sign, *endian)
             },
+            SearchMode::String(encoding, case) => {
+                SearchMode::String(encoding.next(), *case)
+            },
+            SearchMode::Float(size, endian) => {
+                SearchMode::Float(size.next(), *endian)
+            },
             other => *other
         }
     }
@@ -422,6 +724,9 @@ impl SearchMode {
             SearchMode::Integer(size, sign, endian) => {
                 SearchMode::Integer(*size, sign.next(), *endian)
             },
+            SearchMode::String(encoding, case) => {
+                SearchMode::String(*encoding, case.next())
+            },
             other => *other
         }
     }
@@ -434,11 +739,87 @@ impl SearchMode {
             SearchMode::Integer(size, sign, Endian::Big) => {
                 SearchMode::Integer(*size, *sign, Endian::Little)
             },
+            SearchMode::Float(size, Endian::Little) => {
+                SearchMode::Float(*size, Endian::Big)
+            },
+            SearchMode::Float(size, Endian::Big) => {
+                SearchMode::Float(*size, Endian::Little)
+            },
+            SearchMode::String(TextEncoding::Utf16Le, case) => {
+                SearchMode::String(TextEncoding::Utf16Be, *case)
+            },
+            SearchMode::String(TextEncoding::Utf16Be, case) => {
+                SearchMode::String(TextEncoding::Utf16Le, *case)
+            },
             other => *other
         }
     }
 }
 
+// "3.14" or "3.14 ~ 0.001" -> value and epsilon
+pub fn parse_float(input: &[char]) -> Result<(f64, Option<f64>)> {
+    let input: String = input.iter().collect();
+    let (value, epsilon) = match input.split_once('~') {
+        Some((value, epsilon)) => (value.trim(), Some(epsilon.trim())),
+        None => (input.trim(), None),
+    };
+    let illegal = || Error::message(format!("illegal floating point number: {:?}", input));
+
+    let value = if value.is_empty() { 0.0 } else { value.parse::<f64>().map_err(|_| illegal())? };
+    let epsilon = if let Some(epsilon) = epsilon {
+        let epsilon = epsilon.parse::<f64>().map_err(|_| illegal())?;
+        if !(epsilon >= 0.0 && epsilon.is_finite()) {
+            return Err(Error::message(format!("illegal epsilon: {}", epsilon)));
+        }
+        Some(epsilon)
+    } else {
+        None
+    };
+
+    Ok((value, epsilon))
+}
+
+fn parse_nibble(ch: char) -> Option<(u8, u8)> {
+    if ch == '?' {
+        Some((0, 0))
+    } else {
+        ch.to_digit(16).map(|digit| (digit as u8, 0xF))
+    }
+}
+
+// "4D 5A ?? ?F" -> value and mask, ? matches any nibble
+pub fn parse_hex_pattern(input: &[char]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let illegal = || Error::message(format!(
+        "illegal byte in hex string: {:?}",
+        input.iter().collect::<String>()));
+
+    let mut value = Vec::new();
+    let mut mask  = Vec::new();
+    let mut iter = input.iter();
+    while let Some(ch) = iter.next() {
+        let (mut byte, mut bits) = parse_nibble(*ch).ok_or_else(illegal)?;
+        if let Some(ch) = iter.next() {
+            let (low_byte, low_bits) = parse_nibble(*ch).ok_or_else(illegal)?;
+            byte = byte << 4 | low_byte;
+            bits = bits << 4 | low_bits;
+            value.push(byte);
+            mask.push(bits);
+            match iter.next() {
+                Some(' ') => {},
+                Some(_) => return Err(illegal()),
+                None => break,
+            }
+        } else {
+            // a single digit is the low nibble
+            value.push(byte);
+            mask.push(0xF0 | bits);
+            break;
+        }
+    }
+
+    Ok((value, mask))
+}
+
 pub struct SearchWidget {
     buf: Vec<char>,
     focused: bool,
@@ -448,6 +829,12 @@ pub struct SearchWidget {
     history: VecDeque<(SearchMode, Vec<char>)>,
     future:  VecDeque<(SearchMode, Vec<char>)>,
     mode: SearchMode,
+    // why the last Enter beeped
+    error: Option<String>,
+    // the input is used as is, e.g. as a replacement
+    literal: bool,
+    in_selection: bool,
+    wrap: bool,
 }
 
 impl SearchWidget {
@@ -460,18 +847,64 @@ impl SearchWidget {
             view_offset: 0,
             history: VecDeque::new(),
             future:  VecDeque::new(),
-            mode: SearchMode::String,
+            mode: SearchMode::TEXT,
+            error: None,
+            literal: false,
+            in_selection: false,
+            wrap: false,
+        }
+    }
+
+    // Takes the bytes a match is replaced with, so there are no regular
+    // expressions and the input may be empty.
+    pub fn replacement(size: usize) -> Self {
+        let mut widget = Self::new(size);
+        widget.literal = true;
+        widget
+    }
+
+    pub fn with_history(mut self, history: Vec<(SearchMode, Vec<char>)>) -> Self {
+        self.history = history.into();
+        self
+    }
+
+    #[inline]
+    pub fn mode(&self) -> SearchMode {
+        self.mode
+    }
+
+    // Only search within the selection.
+    #[inline]
+    pub fn in_selection(&self) -> bool {
+        self.in_selection
+    }
+
+    // Continue at the other end if nothing is found.
+    #[inline]
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    // [ UTF-8        ] [ Sel Wrap ]
+    fn label(&self) -> String {
+        let mut label = format!(" [ {:<width$} ]", self.mode, width = MODE_WIDTH);
+        if !self.literal {
+            label.push_str(if self.in_selection { " [ Sel " } else { " [ --- " });
+            label.push_str(if self.wrap { "Wrap ]" } else { "---- ]" });
         }
+        label
     }
 
     pub fn set_search_mode(&mut self, mode: SearchMode) {
         if self.mode != mode {
             match mode {
-                SearchMode::String => {
+                SearchMode::String(_, _) => {
                     if self.mode == SearchMode::Binary {
                         if let Ok(bytes) = self.mode.parse(&self.buf) {
-                            if bytes.iter().all(|byte| is_printable_ascii(*byte)) {
-                                self.buf = bytes.iter().map(|byte| *byte as char).collect();
+                            if let Ok(text) = mode.stringify(&bytes) {
+                                if text.chars().all(|ch| !ch.is_control()) {
+                                    self.buf = text.chars().collect();
+                                }
                             }
                         }
                     }
@@ -479,15 +912,41 @@ impl SearchWidget {
                 }
                 SearchMode::Binary => {
                     match self.mode {
-                        SearchMode::String => {
-                            if let Ok(buf) = mode.stringify(self.buf.iter().collect::<String>().as_bytes()) {
+                        SearchMode::String(_, _) => {
+                            if let Ok(buf) = self.mode.parse(&self.buf).and_then(|bytes| mode.stringify(&bytes)) {
                                 self.buf = buf.chars().collect();
                             } else {
                                 self.buf.clear();
                             }
                         },
                         SearchMode::Binary => { /* keep */ }
-                        SearchMode::Integer(_, _, _) => {
+                        SearchMode::Regex  => {
+                            self.buf.clear();
+                        }
+                        SearchMode::Integer(_, _, _) | SearchMode::Float(_, _) => {
+                            if let Ok(bytes) = self.mode.parse(&self.buf) {
+                                if let Ok(buf) = mode.stringify(&bytes) {
+                                    self.buf = buf.chars().collect();
+                                } else {
+                                    self.buf.clear();
+                                }
+                            } else {
+                                self.buf.clear();
+                            }
+                        }
+                    }
+                }
+                SearchMode::Regex => {
+                    match self.mode {
+                        SearchMode::String(_, _) => {
+                            if let Ok(buf) = self.mode.parse(&self.buf).and_then(|bytes| mode.stringify(&bytes)) {
+                                self.buf = buf.chars().collect();
+                            } else {
+                                self.buf.clear();
+                            }
+                        }
+                        SearchMode::Regex => { /* keep */ }
+                        SearchMode::Binary | SearchMode::Integer(_, _, _) | SearchMode::Float(_, _) => {
                             if let Ok(bytes) = self.mode.parse(&self.buf) {
                                 if let Ok(buf) = mode.stringify(&bytes) {
                                     self.buf = buf.chars().collect();
@@ -500,6 +959,23 @@ impl SearchWidget {
                         }
                     }
                 }
+                SearchMode::Float(_, _) => {
+                    match self.mode {
+                        SearchMode::Binary => {
+                            if let Ok(buf) = self.mode.parse(&self.buf).and_then(|bytes| mode.stringify(&bytes)) {
+                                self.buf = buf.chars().collect();
+                            } else {
+                                self.buf.clear();
+                            }
+                        }
+                        SearchMode::Float(_, _) => { /* keep */ }
+                        SearchMode::String(_, _) | SearchMode::Regex | SearchMode::Integer(_, _, _) => {
+                            if parse_float(&self.buf).is_err() {
+                                self.buf.clear();
+                            }
+                        }
+                    }
+                }
                 SearchMode::Integer(to_size, to_sign, _) => {
                     match self.mode {
                         SearchMode::Binary => {
@@ -513,7 +989,7 @@ impl SearchWidget {
                                 self.buf.clear();
                             }
                         }
-                        SearchMode::String => {
+                        SearchMode::String(_, _) | SearchMode::Regex | SearchMode::Float(_, _) => {
                             if to_sign.is_signed() {
                                 if let Ok(num) = self.buf.iter().collect::<String>().parse::<i64>() {
                                     self.buf = format!("{}", num).chars().collect();
@@ -580,10 +1056,11 @@ impl SearchWidget {
     }
 
     fn adjust_view(&mut self) {
-        if self.size <= 16 {
+        let label_len = self.label().len() + 1;
+        if self.size <= label_len {
             self.view_offset = 0;
         } else {
-            let size = self.size - 16;
+            let size = self.size - label_len;
 
             if self.cursor > self.view_offset + size {
                 self.view_offset = self.cursor - size;
@@ -626,9 +1103,22 @@ impl SearchWidget {
         Ok(())
     }
 
-    #[allow(unused)]
-    pub fn value(&self) -> Result<Vec<u8>> {
-        self.mode.parse(&self.buf)
+    pub fn value(&self) -> Result<Pattern> {
+        if self.literal {
+            self.mode.parse(&self.buf).map(Pattern::bytes)
+        } else {
+            self.mode.pattern(&self.buf)
+        }
+    }
+
+    #[inline]
+    pub fn text(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    #[inline]
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
     }
 
     pub fn set_mode_and_value(&mut self, mode: SearchMode, value: &[u8]) -> Result<()> {
@@ -642,7 +1132,7 @@ impl SearchWidget {
     }
 }
 
-impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
+impl InputWidget<&[u8], Pattern> for SearchWidget {
     fn has_focus(&self) -> bool {
         self.focused
     }
@@ -669,11 +1159,12 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
 
     fn redraw<P>(&self, window: &mut Window, pos: P) -> Result<()>
     where P: Into<Point>, P: Copy {
-        // [ Binary     ]
-        // [ Text       ]
-        // [ UInt 64 LE ]
-        if self.size <= 16 {
-            let line = format!("  [ {:<10} ]", self.mode);
+        // [ Binary       ]
+        // [ UTF-16LE ui  ]
+        // [ UInt 64 LE   ]
+        let label = self.label();
+        if self.size <= label.len() + 1 {
+            let line = format!(" {}", label);
             let _ = window.put_str(&line[line.len() - self.size..]);
             return Ok(());
         }
@@ -688,7 +1179,7 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
             len += 1;
         }
 
-        let size = self.size - 16;
+        let size = self.size - (label.len() + 1);
         if len > size {
             if self.view_offset > buf.len() {
                 // should not happen
@@ -714,12 +1205,12 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
             }
         }
 
-        let _ = window.put_str(format!(" [ {:<10} ]", self.mode));
+        let _ = window.put_str(label);
 
         Ok(())
     }
 
-    fn handle(&mut self, input: Input) -> Result<WidgetResult<Vec<u8>>> {
+    fn handle(&mut self, input: Input) -> Result<WidgetResult<Pattern>> {
         if !self.focused {
             return Ok(WidgetResult::PropagateEvent);
         }
@@ -768,7 +1259,7 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                 return Ok(WidgetResult::Redraw);
             }
             Input::Character('\n') | Input::KeyF3 => {
-                if self.buf.is_empty() {
+                if self.buf.is_empty() && !self.literal {
                     return Ok(WidgetResult::Beep);
                 }
                 if self.future.len() > 0 {
@@ -787,14 +1278,23 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                     self.history.push_back((self.mode, self.buf.clone()));
                 }
 
-                if let Ok(bytes) = self.mode.parse(&self.buf) {
-                    self.focused = false;
-                    return Ok(WidgetResult::Value(bytes));
+                match self.value() {
+                    Ok(pattern) => {
+                        self.focused = false;
+                        return Ok(WidgetResult::Value(pattern));
+                    }
+                    Err(error) => {
+                        self.error = Some(error.to_string());
+                        return Ok(WidgetResult::Beep);
+                    }
                 }
-                return Ok(WidgetResult::Beep);
             }
             Input::Character(END_OF_MEDIUM) => {
-                self.set_search_mode(self.mode.prev_major());
+                let mut mode = self.mode.prev_major();
+                if self.literal && mode.is_regex() {
+                    mode = mode.prev_major();
+                }
+                self.set_search_mode(mode);
                 return Ok(WidgetResult::Redraw);
             }
             Input::Character(mut ch) => {
@@ -821,7 +1321,26 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                             }
                         }
                     }
-                    SearchMode::String => {
+                    SearchMode::Float(_, _) => {
+                        if ch == 'q' {
+                            self.focused = false;
+                            return Ok(WidgetResult::Redraw);
+                        } else if !"0123456789+-.eE~ infINFaA".contains(ch) {
+                            return Ok(WidgetResult::PropagateEvent);
+                        }
+                        // incomplete numbers like "1e" are only checked on Enter
+                        self.buf.insert(self.cursor, ch);
+                        self.cursor += 1;
+                    }
+                    SearchMode::String(encoding, _) => {
+                        let mut data = Vec::new();
+                        if encoding.encode(ch, &mut data).is_err() {
+                            return Ok(WidgetResult::Beep);
+                        }
+                        self.buf.insert(self.cursor, ch);
+                        self.cursor += 1;
+                    }
+                    SearchMode::Regex => {
                         self.buf.insert(self.cursor, ch);
                         self.cursor += 1;
                     }
@@ -831,7 +1350,7 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                             return Ok(WidgetResult::Redraw);
                         } else if ch >= 'a' && ch <= 'f' {
                             ch.make_ascii_uppercase();
-                        } else if !((ch >= '0' && ch <= '9') || (ch >= 'A' && ch <= 'F')) {
+                        } else if !((ch >= '0' && ch <= '9') || (ch >= 'A' && ch <= 'F') || ch == '?') {
                             return Ok(WidgetResult::PropagateEvent);
                         }
 
@@ -887,7 +1406,7 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
             Input::KeyDC => {
                 if self.cursor < self.buf.len() {
                     match self.mode {
-                        SearchMode::String | SearchMode::Integer(_, _, _) => {
+                        SearchMode::String(_, _) | SearchMode::Regex | SearchMode::Integer(_, _, _) | SearchMode::Float(_, _) => {
                             self.buf.remove(self.cursor);
                         }
                         SearchMode::Binary => {
@@ -913,7 +1432,7 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
             Input::KeyBackspace => {
                 if self.cursor > 0 {
                     match self.mode {
-                        SearchMode::String | SearchMode::Integer(_, _, _) => {
+                        SearchMode::String(_, _) | SearchMode::Regex | SearchMode::Integer(_, _, _) | SearchMode::Float(_, _) => {
                             self.buf.remove(self.cursor - 1);
                             self.cursor -= 1;
                         }
@@ -945,7 +1464,11 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                 return Ok(WidgetResult::Ignore);
             }
             Input::KeyF5 => {
-                self.set_search_mode(self.mode.next_major());
+                let mut mode = self.mode.next_major();
+                if self.literal && mode.is_regex() {
+                    mode = mode.next_major();
+                }
+                self.set_search_mode(mode);
                 return Ok(WidgetResult::Redraw);
             }
             Input::KeyF6 => {
@@ -960,6 +1483,14 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
                 self.set_search_mode(self.mode.next_endian());
                 return Ok(WidgetResult::Redraw);
             }
+            Input::KeyF9 if !self.literal => {
+                self.in_selection = !self.in_selection;
+                return Ok(WidgetResult::Redraw);
+            }
+            Input::KeyF10 if !self.literal => {
+                self.wrap = !self.wrap;
+                return Ok(WidgetResult::Redraw);
+            }
             Input::KeyUp => {
                 if self.history.is_empty() {
                     return Ok(WidgetResult::Ignore);
@@ -1000,3 +1531,109 @@ impl InputWidget<&[u8], Vec<u8>> for SearchWidget {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::bytes::Regex;
+
+    fn chars(input: &str) -> Vec<char> {
+        input.chars().collect()
+    }
+
+    #[test]
+    fn hex_pattern() {
+        assert_eq!(parse_hex_pattern(&chars("")).unwrap(), (vec![], vec![]));
+        assert_eq!(parse_hex_pattern(&chars("4D 5a")).unwrap(), (vec![0x4D, 0x5A], vec![0xFF, 0xFF]));
+        assert_eq!(parse_hex_pattern(&chars("?F")).unwrap(), (vec![0x0F], vec![0x0F]));
+        assert_eq!(parse_hex_pattern(&chars("F?")).unwrap(), (vec![0xF0], vec![0xF0]));
+        assert_eq!(parse_hex_pattern(&chars("??")).unwrap(), (vec![0x00], vec![0x00]));
+    }
+
+    #[test]
+    fn hex_pattern_trailing_digit() {
+        assert_eq!(parse_hex_pattern(&chars("4D 5")).unwrap(), (vec![0x4D, 0x05], vec![0xFF, 0xFF]));
+        assert_eq!(parse_hex_pattern(&chars("4D ")).unwrap(), (vec![0x4D], vec![0xFF]));
+        assert_eq!(parse_hex_pattern(&chars("?")).unwrap(), (vec![0x00], vec![0xF0]));
+    }
+
+    #[test]
+    fn hex_pattern_invalid() {
+        for input in ["4G", "4D5A", "4D  5A", " 4D", "x"] {
+            assert!(parse_hex_pattern(&chars(input)).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn case_variants() {
+        assert_eq!(Case::Sensitive.variants('a'), vec!['a']);
+        assert_eq!(Case::IgnoreAscii.variants('a'), vec!['a', 'A']);
+        assert_eq!(Case::IgnoreAscii.variants('Ä'), vec!['Ä']);
+        assert_eq!(Case::IgnoreUnicode.variants('Ä'), vec!['Ä', 'ä']);
+        // ß uppercases to SS, which isn't a single character
+        assert_eq!(Case::IgnoreUnicode.variants('ß'), vec!['ß']);
+        assert_eq!(Case::IgnoreUnicode.variants('ẞ'), vec!['ẞ', 'ß']);
+        // the Kelvin sign lowercases to k, but nothing uppercases to it
+        assert_eq!(Case::IgnoreUnicode.variants('K'), vec!['K', 'k']);
+        assert_eq!(Case::IgnoreUnicode.variants('\u{212A}'), vec!['\u{212A}', 'k', 'K']);
+    }
+
+    fn case_regex(mode: SearchMode, input: &str) -> (Regex, usize) {
+        match mode.pattern(&chars(input)).unwrap() {
+            Pattern::Regex(regex, max_len) => (regex, max_len),
+            pattern => panic!("not a regex: {:?}", pattern),
+        }
+    }
+
+    #[test]
+    fn ignore_case_pattern() {
+        let (regex, max_len) = case_regex(SearchMode::String(TextEncoding::Utf8, Case::IgnoreUnicode), "aä");
+        assert_eq!(max_len, 3);
+        assert!(regex.is_match("xAÄ".as_bytes()));
+        assert!(!regex.is_match("ae".as_bytes()));
+
+        // ẞ takes three bytes in UTF-8, ß only two
+        let (_, max_len) = case_regex(SearchMode::String(TextEncoding::Utf8, Case::IgnoreUnicode), "ẞ");
+        assert_eq!(max_len, 3);
+
+        let (regex, max_len) = case_regex(SearchMode::String(TextEncoding::Latin1, Case::IgnoreUnicode), "ä\u{212A}");
+        assert_eq!(max_len, 2);
+        assert!(regex.is_match(b"\xC4K"));
+        assert!(regex.is_match(b"\xE4k"));
+
+        let (regex, max_len) = case_regex(SearchMode::String(TextEncoding::Utf16Le, Case::IgnoreUnicode), "ä");
+        assert_eq!(max_len, 2);
+        assert!(regex.is_match(b"\xC4\x00"));
+        assert!(!regex.is_match(b"\x00\xC4"));
+
+        let (regex, _) = case_regex(SearchMode::String(TextEncoding::Utf16Be, Case::IgnoreAscii), "a");
+        assert!(regex.is_match(b"\x00A"));
+
+        // not encodable at all
+        assert!(SearchMode::String(TextEncoding::Latin1, Case::IgnoreUnicode).pattern(&chars("Ω")).is_err());
+    }
+
+    #[test]
+    fn float() {
+        assert_eq!(parse_float(&chars("2.5")).unwrap(), (2.5, None));
+        assert_eq!(parse_float(&chars(" -1e3 ")).unwrap(), (-1000.0, None));
+        assert_eq!(parse_float(&chars("")).unwrap(), (0.0, None));
+        assert_eq!(parse_float(&chars("inf")).unwrap(), (f64::INFINITY, None));
+        assert_eq!(parse_float(&chars("-inf")).unwrap(), (f64::NEG_INFINITY, None));
+        assert!(parse_float(&chars("NaN")).unwrap().0.is_nan());
+    }
+
+    #[test]
+    fn float_epsilon() {
+        assert_eq!(parse_float(&chars("2.5 ~ 0.001")).unwrap(), (2.5, Some(0.001)));
+        assert_eq!(parse_float(&chars("2.5~0")).unwrap(), (2.5, Some(0.0)));
+        assert_eq!(parse_float(&chars("~ 0.5")).unwrap(), (0.0, Some(0.5)));
+    }
+
+    #[test]
+    fn float_invalid() {
+        for input in ["abc", "1.2.3", "1 ~", "1 ~ x", "1 ~ -0.5", "1 ~ inf", "1 ~ nan", "1 ~ 2 ~ 3"] {
+            assert!(parse_float(&chars(input)).is_err(), "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+
+use crate::result::Result;
+
+const BLOCK_SIZE: usize = 64 * 1024;
+
+struct SpoolState {
+    len:   AtomicU64,
+    done:  AtomicBool,
+    error: Mutex<Option<std::io::Error>>,
+}
+
+// Copies a stream (pipe, FIFO, ...) into an unlinked temporary file in a
+// background thread, so it can be viewed while data is still arriving.
+pub struct Spool {
+    file:  File,
+    state: Arc<SpoolState>,
+}
+
+impl Spool {
+    pub fn new(mut input: File) -> Result<Self> {
+        let file = temp_file()?;
+        let mut writer = file.try_clone()?;
+
+        let state = Arc::new(SpoolState {
+            len:   AtomicU64::new(0),
+            done:  AtomicBool::new(false),
+            error: Mutex::new(None),
+        });
+
+        let thread_state = state.clone();
+        std::thread::spawn(move || {
+            let mut buf = vec![0; BLOCK_SIZE];
+            loop {
+                match input.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(count) => {
+                        if let Err(error) = writer.write_all(&buf[..count]) {
+                            *thread_state.error.lock().unwrap() = Some(error);
+                            break;
+                        }
+                        thread_state.len.fetch_add(count as u64, Ordering::Release);
+                    }
+                    Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(error) => {
+                        *thread_state.error.lock().unwrap() = Some(error);
+                        break;
+                    }
+                }
+            }
+            thread_state.done.store(true, Ordering::Release);
+        });
+
+        Ok(Self { file, state })
+    }
+
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.state.len.load(Ordering::Acquire)
+    }
+
+    // Shares the state with the reading thread.
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(Self {
+            file:  self.file.try_clone()?,
+            state: self.state.clone(),
+        })
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.state.done.load(Ordering::Acquire)
+    }
+
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.state.error.lock().unwrap().take()
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        self.file.read_exact_at(buf, offset)
+    }
+}
+
+fn temp_file() -> std::io::Result<File> {
+    let dir = std::env::temp_dir();
+    let mut index = 0;
+    loop {
+        let path = dir.join(format!(".hox-spool-{}-{}", std::process::id(), index));
+        match std::fs::OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(file) => {
+                std::fs::remove_file(&path)?;
+                return Ok(file);
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                index += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// Curses needs the terminal on stdin, so the data is read from a duplicate of
+// stdin and the terminal is put in its place.
+pub fn take_stdin() -> Result<File> {
+    let tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+
+    unsafe {
+        let fd = libc::dup(libc::STDIN_FILENO);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let input = File::from_raw_fd(fd);
+
+        if libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use std::time::{Duration, Instant};
+
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    fn wait_for(what: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !what() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn temp_file_is_unlinked() {
+        let file = temp_file().unwrap();
+        assert_eq!(file.metadata().unwrap().nlink(), 0);
+    }
+
+    #[test]
+    fn grows_while_reading() {
+        let (input, mut output) = pipe();
+        let spool = Spool::new(input).unwrap();
+        let clone = spool.try_clone().unwrap();
+        assert_eq!(spool.len(), 0);
+
+        output.write_all(b"hello ").unwrap();
+        wait_for(|| spool.len() == 6);
+        let mut buf = [0; 6];
+        spool.read(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"hello ");
+
+        // nothing past the spooled length
+        let mut buf = [0; 4];
+        assert_eq!(spool.read(4, &mut buf).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(!spool.is_done());
+
+        // more than one block
+        let data: Vec<u8> = (0..BLOCK_SIZE * 3).map(|index| index as u8).collect();
+        output.write_all(&data).unwrap();
+        drop(output);
+        wait_for(|| spool.is_done());
+        assert_eq!(spool.len(), 6 + data.len() as u64);
+        assert_eq!(clone.len(), spool.len());
+        assert!(spool.take_error().is_none());
+
+        let mut buf = vec![0; data.len()];
+        clone.read(6, &mut buf).unwrap();
+        assert_eq!(buf, data);
+        let mut buf = [0; 4];
+        spool.read(2, &mut buf).unwrap();
+        assert_eq!(&buf, b"llo ");
+        assert!(spool.read(spool.len(), &mut buf).is_err());
+    }
+}
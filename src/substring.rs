@@ -0,0 +1,397 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+// Fast search for a fixed byte string. Only uses std so that the benchmark in
+// benches/ can include this file directly.
+
+use std::mem::size_of;
+
+const WORD: usize = size_of::<usize>();
+const LO: usize = usize::from_ne_bytes([0x01; WORD]);
+const HI: usize = usize::from_ne_bytes([0x80; WORD]);
+
+// The needle is first searched for by one of its rarest bytes. If that byte
+// turns out to be frequent in the data too, the rest of the data is searched
+// with Boyer-Moore-Horspool.
+const PREFILTER_MIN_SKIP: usize = 16;
+const PREFILTER_MAX_FAILS: usize = 8;
+
+// Rough guess how often a byte appears in typical files, higher is more often.
+fn byte_rank(byte: u8) -> u8 {
+    match byte {
+        0x00 => 255,
+        0xFF => 200,
+        b' ' => 180,
+        b'a'..=b'z' => 150,
+        b'A'..=b'Z' | b'0'..=b'9' | b'\n' | 0x01 => 120,
+        0x02..=0x1F | 0x21..=0x2F | 0x3A..=0x40 | 0x80 | 0xFE => 80,
+        _ => 50,
+    }
+}
+
+#[inline]
+fn has_zero_byte(word: usize) -> bool {
+    word.wrapping_sub(LO) & !word & HI != 0
+}
+
+#[inline]
+fn read_word(data: &[u8]) -> usize {
+    usize::from_ne_bytes(data.try_into().unwrap())
+}
+
+// Index of the first byte in haystack equal to needle. Compares a whole word
+// at a time, two words per iteration.
+pub fn memchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO.wrapping_mul(needle as usize);
+    let mut index = 0;
+
+    while index + 2 * WORD <= haystack.len() {
+        let a = read_word(&haystack[index..index + WORD]) ^ repeated;
+        let b = read_word(&haystack[index + WORD..index + 2 * WORD]) ^ repeated;
+        if has_zero_byte(a) || has_zero_byte(b) {
+            break;
+        }
+        index += 2 * WORD;
+    }
+
+    haystack[index..].iter().position(|byte| *byte == needle).map(|found| index + found)
+}
+
+// Index of the last byte in haystack equal to needle.
+pub fn memrchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO.wrapping_mul(needle as usize);
+    let mut end = haystack.len();
+
+    while end >= 2 * WORD {
+        let a = read_word(&haystack[end - WORD..end]) ^ repeated;
+        let b = read_word(&haystack[end - 2 * WORD..end - WORD]) ^ repeated;
+        if has_zero_byte(a) || has_zero_byte(b) {
+            break;
+        }
+        end -= 2 * WORD;
+    }
+
+    haystack[..end].iter().rposition(|byte| *byte == needle)
+}
+
+// A needle prepared for searching. The shift tables tell how far the window
+// may move depending on the byte under its last (forward) or first (reverse)
+// position.
+#[derive(Clone)]
+pub struct Finder {
+    needle:        Vec<u8>,
+    rare_index:    usize,
+    forward_shift: Box<[usize; 256]>,
+    reverse_shift: Box<[usize; 256]>,
+}
+
+impl std::fmt::Debug for Finder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Finder").field(&self.needle).finish()
+    }
+}
+
+impl Finder {
+    pub fn new(needle: Vec<u8>) -> Self {
+        let len = needle.len();
+        let mut forward_shift = Box::new([len; 256]);
+        let mut reverse_shift = Box::new([len; 256]);
+        let mut rare_index = 0;
+
+        if len > 0 {
+            for (index, byte) in needle[..len - 1].iter().enumerate() {
+                forward_shift[*byte as usize] = len - 1 - index;
+            }
+            for (index, byte) in needle[1..].iter().enumerate().rev() {
+                reverse_shift[*byte as usize] = index + 1;
+            }
+            for (index, byte) in needle.iter().enumerate() {
+                if byte_rank(*byte) < byte_rank(needle[rare_index]) {
+                    rare_index = index;
+                }
+            }
+        }
+
+        Self { needle, rare_index, forward_shift, reverse_shift }
+    }
+
+    #[inline]
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.needle.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.needle.is_empty()
+    }
+
+    // Index of the first occurrence of the needle in haystack.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle[..];
+        let len = needle.len();
+        if len == 0 {
+            return Some(0);
+        }
+        if len > haystack.len() {
+            return None;
+        }
+        if len == 1 {
+            return memchr(needle[0], haystack);
+        }
+
+        let rare_index = self.rare_index;
+        let rare_byte = needle[rare_index];
+        let last_start = haystack.len() - len;
+        let mut start = 0;
+        let mut fails = 0;
+        while start <= last_start {
+            let skip = memchr(rare_byte, &haystack[start + rare_index..=last_start + rare_index])?;
+            let candidate = start + skip;
+            if haystack[candidate..candidate + len] == *needle {
+                return Some(candidate);
+            }
+            start = candidate + 1;
+            if skip < PREFILTER_MIN_SKIP {
+                fails += 1;
+                if fails > PREFILTER_MAX_FAILS {
+                    return self.horspool_find(haystack, start);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn horspool_find(&self, haystack: &[u8], mut start: usize) -> Option<usize> {
+        let needle = &self.needle[..];
+        let last = needle.len() - 1;
+        let last_byte = needle[last];
+        while start + last < haystack.len() {
+            let byte = haystack[start + last];
+            if byte == last_byte && haystack[start..start + last] == needle[..last] {
+                return Some(start);
+            }
+            start += self.forward_shift[byte as usize];
+        }
+
+        None
+    }
+
+    // Index of the last occurrence of the needle in haystack.
+    pub fn rfind(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle[..];
+        let len = needle.len();
+        if len == 0 {
+            return Some(haystack.len());
+        }
+        if len > haystack.len() {
+            return None;
+        }
+        if len == 1 {
+            return memrchr(needle[0], haystack);
+        }
+
+        let rare_index = self.rare_index;
+        let rare_byte = needle[rare_index];
+        // exclusive end of the possible match starts
+        let mut end = haystack.len() - len + 1;
+        let mut fails = 0;
+        while end > 0 {
+            let candidate = memrchr(rare_byte, &haystack[rare_index..end + rare_index])?;
+            if haystack[candidate..candidate + len] == *needle {
+                return Some(candidate);
+            }
+            let skip = end - 1 - candidate;
+            end = candidate;
+            if skip < PREFILTER_MIN_SKIP {
+                fails += 1;
+                if fails > PREFILTER_MAX_FAILS {
+                    return self.horspool_rfind(haystack, end);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn horspool_rfind(&self, haystack: &[u8], end: usize) -> Option<usize> {
+        if end == 0 {
+            return None;
+        }
+        let needle = &self.needle[..];
+        let len = needle.len();
+        let first_byte = needle[0];
+        let mut start = end - 1;
+        loop {
+            let byte = haystack[start];
+            if byte == first_byte && haystack[start + 1..start + len] == needle[1..] {
+                return Some(start);
+            }
+            let shift = self.reverse_shift[byte as usize];
+            if shift > start {
+                return None;
+            }
+            start -= shift;
+        }
+    }
+
+    // Start indices of all, possibly overlapping, occurrences in haystack.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let mut index = 0;
+        std::iter::from_fn(move || {
+            if self.needle.is_empty() || index > haystack.len() {
+                return None;
+            }
+            let found = index + self.find(&haystack[index..])?;
+            index = found + 1;
+            Some(found)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_find(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(0);
+        }
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    fn naive_rfind(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(haystack.len());
+        }
+        haystack.windows(needle.len()).rposition(|window| window == needle)
+    }
+
+    fn naive_find_all(needle: &[u8], haystack: &[u8]) -> Vec<usize> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        haystack.windows(needle.len()).enumerate()
+            .filter(|(_, window)| *window == needle)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn check(needle: &[u8], haystack: &[u8]) {
+        let finder = Finder::new(needle.to_vec());
+        assert_eq!(finder.find(haystack),  naive_find(needle, haystack),  "find {:?} in {:?}", needle, haystack);
+        assert_eq!(finder.rfind(haystack), naive_rfind(needle, haystack), "rfind {:?} in {:?}", needle, haystack);
+        assert_eq!(finder.find_iter(haystack).collect::<Vec<_>>(), naive_find_all(needle, haystack),
+            "find_iter {:?} in {:?}", needle, haystack);
+    }
+
+    // xorshift, so the tests don't need a dependency
+    fn random_bytes(seed: &mut u64, len: usize, alphabet: &[u8]) -> Vec<u8> {
+        (0..len).map(|_| {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            alphabet[(*seed % alphabet.len() as u64) as usize]
+        }).collect()
+    }
+
+    #[test]
+    fn empty_needle() {
+        let finder = Finder::new(Vec::new());
+        assert_eq!(finder.find(b"abc"), Some(0));
+        assert_eq!(finder.rfind(b"abc"), Some(3));
+        assert_eq!(finder.find(b""), Some(0));
+        assert_eq!(finder.find_iter(b"abc").count(), 0);
+    }
+
+    #[test]
+    fn needle_longer_than_haystack() {
+        check(b"abcd", b"abc");
+        check(b"abcd", b"");
+        check(b"a", b"");
+    }
+
+    #[test]
+    fn single_byte() {
+        // long enough to go through the word-wise loops
+        let mut haystack = vec![b'x'; 100];
+        check(b"y", &haystack);
+        for index in [0, 1, 7, 8, 15, 16, 17, 50, 98, 99] {
+            haystack[index] = b'y';
+            check(b"y", &haystack);
+        }
+        check(b"\0", &haystack);
+        check(b"\xFF", &[0xFF; 40]);
+    }
+
+    #[test]
+    fn first_and_last_position() {
+        check(b"ab", b"abxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
+        check(b"ab", b"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxab");
+        check(b"ab", b"abxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxab");
+        check(b"abc", b"abc");
+    }
+
+    #[test]
+    fn overlapping() {
+        check(b"aaaa", b"aaaaaaa");
+        check(b"abab", b"abababxabab");
+    }
+
+    // The rare byte of the needle is everywhere, so the prefilter gives up and
+    // Boyer-Moore-Horspool takes over.
+    #[test]
+    fn horspool_fallback() {
+        let needle = b"ab\x7Fcd";
+        let mut haystack = b"\x7F".repeat(200);
+        check(needle, &haystack);
+        haystack[150..155].copy_from_slice(needle);
+        check(needle, &haystack);
+        haystack[20..25].copy_from_slice(needle);
+        check(needle, &haystack);
+
+        let finder = Finder::new(needle.to_vec());
+        for start in [0, 20, 21, 150, 151, 195] {
+            assert_eq!(finder.horspool_find(&haystack, start), naive_find(needle, &haystack[start..]).map(|index| start + index));
+        }
+        for end in [0, 20, 21, 150, 151, 196] {
+            assert_eq!(finder.horspool_rfind(&haystack, end), naive_rfind(needle, &haystack[..end + needle.len() - 1]));
+        }
+    }
+
+    #[test]
+    fn random() {
+        let mut seed = 0x2545F4914F6CDD1D;
+        for alphabet in [&b"ab"[..], b"ab\0", b"a\x7F\x80\xFE"] {
+            for _ in 0..300 {
+                let len = (seed % 80) as usize;
+                let haystack = random_bytes(&mut seed, len, alphabet);
+                let needle_len = (seed % 6) as usize;
+                let needle = random_bytes(&mut seed, needle_len, alphabet);
+                check(&needle, &haystack);
+                // needles taken from the haystack are always found
+                if haystack.len() > 4 {
+                    let start = (seed % (haystack.len() - 4) as u64) as usize;
+                    check(&haystack[start..start + 4], &haystack);
+                }
+            }
+        }
+    }
+}
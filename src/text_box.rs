@@ -13,8 +13,9 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::cmp::min;
-use pancurses_result::{Window, Input, Dimension};
+use std::cmp::{min, max};
+use std::borrow::Cow;
+use pancurses_result::{Window, Input, Dimension, ColorPair};
 
 use crate::result::Result;
 use crate::consts::*;
@@ -25,10 +26,11 @@ pub enum TextBoxResult {
     Redraw,
     Quit,
     Ignore,
+    Selected(usize),
 }
 
 pub struct TextBox<'a> {
-    text: &'a str,
+    text: Cow<'a, str>,
     lines: Vec<String>,
     win_size: Dimension,
     max_line_len: usize,
@@ -37,12 +39,16 @@ pub struct TextBox<'a> {
     hpadding: u32,
     vdiff: u32,
     hdiff: u32,
+    // a list with one selectable entry per line
+    selected: Option<usize>,
+    // shown in the top border
+    title: Option<String>,
 }
 
 impl<'a> TextBox<'a> {
-    pub fn new(text: &'a str, hpadding: u32, vpadding: u32) -> Self {
+    pub fn new(text: impl Into<Cow<'a, str>>, hpadding: u32, vpadding: u32) -> Self {
         Self {
-            text,
+            text: text.into(),
             lines: Vec::new(),
             win_size: Dimension::from((0, 0)),
             max_line_len: 0,
@@ -51,6 +57,40 @@ impl<'a> TextBox<'a> {
             hpadding,
             vdiff: vpadding * 2 + 2,
             hdiff: hpadding * 2 + 2,
+            selected: None,
+            title: None,
+        }
+    }
+
+    pub fn list(text: impl Into<Cow<'a, str>>, hpadding: u32, vpadding: u32, selected: usize) -> Self {
+        let mut text_box = Self::new(text, hpadding, vpadding);
+        text_box.selected = Some(selected);
+        text_box
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    fn select(&mut self, index: usize) -> TextBoxResult {
+        let index = min(index, self.lines.len().saturating_sub(1));
+        if self.selected == Some(index) {
+            return TextBoxResult::Ignore;
+        }
+        self.selected = Some(index);
+        self.scroll_to_selected();
+        TextBoxResult::Redraw
+    }
+
+    fn scroll_to_selected(&mut self) {
+        if let Some(selected) = self.selected {
+            let page_height = self.page_height();
+            if selected < self.view_offset {
+                self.view_offset = selected;
+            } else if page_height > 0 && selected >= self.view_offset + page_height {
+                self.view_offset = selected + 1 - page_height;
+            }
         }
     }
 
@@ -77,13 +117,23 @@ impl<'a> TextBox<'a> {
             self.win_size.columns = size.columns;
             self.max_line_len = 0;
             if size.columns as usize > self.hdiff as usize {
-                self.lines = wrap_lines(self.text, size.columns as usize - self.hdiff as usize);
+                let max_width = size.columns as usize - self.hdiff as usize;
+                self.lines = if self.selected.is_some() {
+                    // wrapping would break the line to entry mapping
+                    self.text.split('\n').map(|line| line.chars().take(max_width).collect()).collect()
+                } else {
+                    wrap_lines(&self.text, max_width)
+                };
                 for line in &self.lines {
                     let line_len = line.chars().count();
                     if line_len > self.max_line_len {
                         self.max_line_len = line_len;
                     }
                 }
+                if let Some(title) = &self.title {
+                    // title is padded with a border character and a space on each side
+                    self.max_line_len = min(max(self.max_line_len, title.chars().count() + 4), max_width);
+                }
             } else {
                 self.lines.clear();
             }
@@ -95,6 +145,7 @@ impl<'a> TextBox<'a> {
         if self.view_offset > max_view_offset {
             self.view_offset = max_view_offset;
         }
+        self.scroll_to_selected();
 
         Ok(())
     }
@@ -108,12 +159,33 @@ impl<'a> TextBox<'a> {
 
             draw_box(window, x as u32, y as u32, width as u32, height as u32)?;
 
+            if let Some(title) = &self.title {
+                if width > 6 {
+                    let title: String = title.chars().take(width - 6).collect();
+                    window.move_to((y as i32, x as i32 + 2))?;
+                    window.put_char(' ')?;
+                    window.put_str(title)?;
+                    window.put_char(' ')?;
+                }
+            }
+
             let x = x as i32 + 1 + self.hpadding as i32;
             let mut y = y as i32 + 1 + self.vpadding as i32;
+            let mut index = self.view_offset;
             for line in &self.lines[self.view_offset..self.view_offset + height - self.vdiff as usize] {
                 window.move_to((y, x))?;
-                window.put_str(line)?;
+                if self.selected == Some(index) {
+                    window.turn_on_attributes(ColorPair(PAIR_SELECTION))?;
+                    window.put_str(line)?;
+                    for _ in line.chars().count()..self.max_line_len {
+                        window.put_char(' ')?;
+                    }
+                    window.turn_off_attributes(ColorPair(PAIR_SELECTION))?;
+                } else {
+                    window.put_str(line)?;
+                }
                 y += 1;
+                index += 1;
             }
         }
 
@@ -121,6 +193,33 @@ impl<'a> TextBox<'a> {
     }
 
     pub fn handle(&mut self, input: Input) -> Result<TextBoxResult> {
+        if let Some(selected) = self.selected {
+            match input {
+                Input::KeyHome | Input::Character(CANCEL) | Input::Character('0') => {
+                    return Ok(self.select(0));
+                }
+                Input::KeyEnd | Input::Character(DEVICE_CONTROL3) | Input::Character('$') => {
+                    return Ok(self.select(self.lines.len()));
+                }
+                Input::KeyUp => {
+                    return Ok(self.select(selected.saturating_sub(1)));
+                }
+                Input::KeyDown => {
+                    return Ok(self.select(selected + 1));
+                }
+                Input::KeyPPage => {
+                    return Ok(self.select(selected.saturating_sub(self.page_height())));
+                }
+                Input::KeyNPage => {
+                    return Ok(self.select(selected + self.page_height()));
+                }
+                Input::Character('\n') => {
+                    return Ok(TextBoxResult::Selected(selected));
+                }
+                _ => {}
+            }
+        }
+
         match input {
             Input::KeyHome => {
                 self.view_offset = 0;
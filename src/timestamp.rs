@@ -0,0 +1,173 @@
+// This file is part of rust-hox.
+//
+// rust-hox is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-hox is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-hox.  If not, see <https://www.gnu.org/licenses/>.
+
+// Timestamp formats as ISO-8601 UTC. All functions return None for values
+// that don't make a valid date between the years 1 and 9999.
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+// seconds from the epochs of these formats to 1970-01-01
+const FILETIME_EPOCH: i64 = 11_644_473_600;
+const HFS_EPOCH:      i64 =  2_082_844_800;
+const NTP_EPOCH:      i64 =  2_208_988_800;
+
+// Days since 1970-01-01 to year, month, day in the proleptic Gregorian
+// calendar. See http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z   = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+    let day   = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year  = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// digits is the number of fractional second digits shown.
+fn format_time(secs: i64, nanos: u32, digits: usize) -> Option<String> {
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    if !(1..=9999).contains(&year) {
+        return None;
+    }
+
+    let time = secs.rem_euclid(SECS_PER_DAY);
+    let mut result = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, time / 3600, time / 60 % 60, time % 60);
+    if digits > 0 {
+        let fraction = format!("{:09}", nanos);
+        result.push('.');
+        result.push_str(&fraction[..digits]);
+    }
+    result.push('Z');
+
+    Some(result)
+}
+
+pub fn unix_secs(secs: i64) -> Option<String> {
+    format_time(secs, 0, 0)
+}
+
+pub fn unix_millis(millis: i64) -> Option<String> {
+    format_time(millis.div_euclid(1000), (millis.rem_euclid(1000) * 1_000_000) as u32, 3)
+}
+
+pub fn unix_micros(micros: i64) -> Option<String> {
+    format_time(micros.div_euclid(1_000_000), (micros.rem_euclid(1_000_000) * 1000) as u32, 6)
+}
+
+// Windows FILETIME: 100 ns intervals since 1601-01-01.
+pub fn filetime(ticks: u64) -> Option<String> {
+    format_time((ticks / 10_000_000) as i64 - FILETIME_EPOCH, (ticks % 10_000_000) as u32 * 100, 7)
+}
+
+// HFS+: seconds since 1904-01-01.
+pub fn hfs(secs: u32) -> Option<String> {
+    format_time(secs as i64 - HFS_EPOCH, 0, 0)
+}
+
+// NTP: seconds since 1900-01-01 in the upper 32 bits and a binary fraction of
+// a second in the lower 32 bits.
+pub fn ntp(value: u64) -> Option<String> {
+    let secs     = (value >> 32) as i64 - NTP_EPOCH;
+    let fraction = value & 0xFFFF_FFFF;
+    format_time(secs, ((fraction * 1_000_000_000) >> 32) as u32, 6)
+}
+
+// MS-DOS/FAT: date in the upper 16 bits, time in the lower 16 bits. The time
+// has a resolution of two seconds and no time zone, so it is shown as is.
+pub fn dos(value: u32) -> Option<String> {
+    let date = value >> 16;
+    let time = value & 0xFFFF;
+
+    let year   = 1980 + (date >> 9);
+    let month  = (date >> 5) & 0xF;
+    let day    = date & 0x1F;
+    let hour   = time >> 11;
+    let minute = (time >> 5) & 0x3F;
+    let second = (time & 0x1F) * 2;
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if leap { 29 } else { 28 },
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    Some(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, hour, minute, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dos_value(year: u32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> u32 {
+        ((year - 1980) << 25) | (month << 21) | (day << 16) | (hour << 11) | (minute << 5) | (second / 2)
+    }
+
+    #[test]
+    fn unix() {
+        assert_eq!(unix_secs(0).unwrap(), "1970-01-01T00:00:00Z");
+        assert_eq!(unix_secs(951_782_400).unwrap(), "2000-02-29T00:00:00Z");
+        assert_eq!(unix_secs(-1).unwrap(), "1969-12-31T23:59:59Z");
+        assert_eq!(unix_millis(-1).unwrap(), "1969-12-31T23:59:59.999Z");
+        assert_eq!(unix_micros(1).unwrap(), "1970-01-01T00:00:00.000001Z");
+        assert_eq!(unix_micros(-1_500_000).unwrap(), "1969-12-31T23:59:58.500000Z");
+    }
+
+    #[test]
+    fn year_range() {
+        assert_eq!(unix_secs(253_402_300_799).unwrap(), "9999-12-31T23:59:59Z");
+        assert_eq!(unix_secs(253_402_300_800), None);
+        assert_eq!(unix_secs(-62_135_596_800).unwrap(), "0001-01-01T00:00:00Z");
+        assert_eq!(unix_secs(-62_135_596_801), None);
+        assert_eq!(unix_secs(i64::MAX), None);
+        assert_eq!(unix_secs(i64::MIN), None);
+    }
+
+    #[test]
+    fn epochs() {
+        assert_eq!(filetime(116_444_736_000_000_000).unwrap(), "1970-01-01T00:00:00.0000000Z");
+        assert_eq!(filetime(0).unwrap(), "1601-01-01T00:00:00.0000000Z");
+        assert_eq!(filetime(1).unwrap(), "1601-01-01T00:00:00.0000001Z");
+        assert_eq!(filetime(u64::MAX), None);
+        assert_eq!(hfs(2_082_844_800).unwrap(), "1970-01-01T00:00:00Z");
+        assert_eq!(hfs(0).unwrap(), "1904-01-01T00:00:00Z");
+        assert_eq!(ntp(0).unwrap(), "1900-01-01T00:00:00.000000Z");
+        assert_eq!(ntp((2_208_988_800 << 32) | 0x8000_0000).unwrap(), "1970-01-01T00:00:00.500000Z");
+    }
+
+    #[test]
+    fn dos_date() {
+        assert_eq!(dos(dos_value(2024, 2, 29, 12, 34, 56)).unwrap(), "2024-02-29T12:34:56");
+        assert_eq!(dos(dos_value(2000, 2, 29, 0, 0, 0)).unwrap(), "2000-02-29T00:00:00");
+        assert_eq!(dos(dos_value(1980, 1, 1, 23, 59, 58)).unwrap(), "1980-01-01T23:59:58");
+        assert_eq!(dos(dos_value(2023, 2, 29, 0, 0, 0)), None);
+        assert_eq!(dos(dos_value(2100, 2, 29, 0, 0, 0)), None);
+        assert_eq!(dos(dos_value(2024, 4, 31, 0, 0, 0)), None);
+        assert_eq!(dos(dos_value(2024, 13, 1, 0, 0, 0)), None);
+        assert_eq!(dos(dos_value(2024, 1, 0, 0, 0, 0)), None);
+        assert_eq!(dos(dos_value(2024, 1, 1, 24, 0, 0)), None);
+        assert_eq!(dos(dos_value(2024, 1, 1, 0, 60, 0)), None);
+        assert_eq!(dos(dos_value(2024, 1, 1, 0, 0, 60)), None);
+        assert_eq!(dos(0), None);
+    }
+}